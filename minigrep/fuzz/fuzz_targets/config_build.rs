@@ -0,0 +1,18 @@
+// 命令行参数解析：输入按 NUL 切分成多个参数，不管参数是什么都只能返回错误而不能 panic
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use minigrep::Config;
+
+fuzz_target!(|data: &[u8]| {
+    // --no-config 让结果不受运行模糊测试的机器上的配置文件影响
+    let mut args = vec!["minigrep".to_string(), "--no-config".to_string()];
+    args.extend(
+        data.split(|&b| b == 0)
+            .map(|arg| String::from_utf8_lossy(arg).into_owned()),
+    );
+
+    if let Ok(config) = Config::build(&args) {
+        assert!(config.build_index || config.generate.is_some() || !config.file_paths.is_empty());
+    }
+});
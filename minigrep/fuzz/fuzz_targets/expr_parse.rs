@@ -0,0 +1,23 @@
+// --expr 的解析和求值：第一行是表达式，剩下的每一行分别求值。
+// 不管表达式嵌套多深都只能返回错误而不能栈溢出
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use minigrep::parse_expr;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let (query, contents) = text.split_once('\n').unwrap_or((&text, ""));
+
+    let expr = match parse_expr(query) {
+        Ok(expr) => expr,
+        Err(_) => return,
+    };
+    for line in contents.lines() {
+        let matched = expr.eval(&|term: &String| line.contains(term.as_str()));
+
+        // 换成其它类型之后结果不变
+        let mapped = expr.clone().try_map(&mut |term: String| Ok::<_, ()>(line.contains(&term))).unwrap();
+        assert_eq!(matched, mapped.eval(&|&found| found));
+    }
+});
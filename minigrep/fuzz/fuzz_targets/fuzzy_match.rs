@@ -0,0 +1,34 @@
+// 模糊匹配的查询编译和搜索：第一个字节决定编辑距离和是否忽略大小写，
+// 之后的第一行是查询，剩下的每一行分别搜索
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use minigrep::FuzzyMatcher;
+
+fuzz_target!(|data: &[u8]| {
+    let (&flags, rest) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let max_distance = (flags & 0x07) as usize;
+    let ignore_case = flags & 0x08 != 0;
+
+    let text = String::from_utf8_lossy(rest);
+    let (query, contents) = text.split_once('\n').unwrap_or((&text, ""));
+
+    // 查询太长时返回错误
+    let matcher = match FuzzyMatcher::new(query, max_distance, ignore_case) {
+        Ok(matcher) => matcher,
+        Err(_) => return,
+    };
+
+    for line in contents.lines() {
+        if let Some(found) = matcher.find(line) {
+            assert!(found.distance <= max_distance);
+            assert!(found.start <= found.end && found.end <= line.len());
+            // 返回的范围必须落在字符边界上，输出时会用它切片高亮
+            assert!(line.is_char_boundary(found.start) && line.is_char_boundary(found.end));
+            assert!(matcher.is_match(line));
+        }
+    }
+});
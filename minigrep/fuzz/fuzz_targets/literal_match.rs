@@ -0,0 +1,37 @@
+// 普通匹配和忽略大小写的匹配：第一行是查询，剩下的是要搜索的内容
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use minigrep::{search, search_bytes, search_case_insensitive, LineMatcher};
+
+fuzz_target!(|data: &[u8]| {
+    let (query, contents) = match data.iter().position(|&b| b == b'\n') {
+        Some(end) => (&data[..end], &data[end + 1..]),
+        None => (data, &[][..]),
+    };
+
+    // 不是 UTF-8 的内容走按字节匹配的路径
+    let bytes_results = search_bytes(query, contents);
+    if let Ok(query) = std::str::from_utf8(query) {
+        // 逐行读取文件时用的 LineMatcher 要和 search_bytes 的结果一致
+        let matcher = LineMatcher::new(query, false);
+        for line in &bytes_results {
+            assert!(matcher.is_match_bytes(line));
+        }
+    }
+
+    if let (Ok(query), Ok(contents)) = (std::str::from_utf8(query), std::str::from_utf8(contents)) {
+        let sensitive = search(query, contents);
+        let insensitive = search_case_insensitive(query, contents);
+
+        // 和最直接的写法比较：标准库切分行，str::contains 判断
+        let expected: Vec<&str> = contents.lines().filter(|line| line.contains(query)).collect();
+        assert_eq!(expected, sensitive);
+        let expected_bytes: Vec<&[u8]> = expected.iter().map(|line| line.as_bytes()).collect();
+        assert_eq!(expected_bytes, bytes_results);
+
+        // 区分大小写能匹配的行，忽略大小写时一定也能匹配
+        let mut rest = insensitive.iter();
+        assert!(sensitive.iter().all(|line| rest.any(|other| other == line)));
+    }
+});
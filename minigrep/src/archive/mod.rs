@@ -0,0 +1,85 @@
+// 把 tar 和 zip 归档当作虚拟目录，逐个搜索其中的文件，
+// 结果中的路径写成 `归档文件!/归档中的路径`
+use std::path::Path;
+
+use crate::decompress::{self, Format};
+
+mod tar;
+mod zip;
+
+// 归档中的一个普通文件，目录和链接等不会出现在这里
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+// 和压缩文件一样通过文件开头的魔数识别，head 是文件的开头，至少要有一个 tar 头部（512 字节）才能认出 tar。
+// gzip 压缩的 tar 要解压之后才能确认，所以只认 .tar.gz 和 .tgz 扩展名，避免把每个 .gz 文件都解压一遍
+pub fn is_archive(head: &[u8], path: &Path) -> bool {
+    let name = path.as_os_str().as_encoded_bytes();
+    zip::is_zip(head)
+        || tar::is_tar(head)
+        || (decompress::detect(head) == Format::Gzip && (name.ends_with(b".tar.gz") || name.ends_with(b".tgz")))
+}
+
+// 列出归档中所有的文件
+pub fn members(bytes: Vec<u8>) -> Result<Vec<Member>, &'static str> {
+    if zip::is_zip(&bytes) {
+        return zip::members(&bytes);
+    }
+
+    let bytes = decompress::decompress(bytes)?;
+    if tar::is_tar(&bytes) {
+        tar::members(&bytes)
+    } else {
+        Err("not a tar or zip archive")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POEM: &[u8] = include_bytes!("../../poem.txt");
+    const RUST: &[u8] = b"Rust:\nsafe, fast, productive.\nPick three.\n";
+
+    fn paths(members: &[Member]) -> Vec<&str> {
+        members.iter().map(|member| member.path.as_str()).collect()
+    }
+
+    #[test]
+    fn tar_members() {
+        let long = format!("src/{}rust.txt", "very_long_directory_name/".repeat(5));
+        for bytes in [
+            include_bytes!("../../tests/fixtures/bundle.tar").to_vec(),
+            include_bytes!("../../tests/fixtures/bundle.tar.gz").to_vec(),
+        ] {
+            let members = members(bytes).unwrap();
+            // GNU 格式的长文件名保存在单独的 'L' 记录中，目录不算文件
+            assert_eq!(vec!["docs/poem.txt", long.as_str()], paths(&members));
+            assert_eq!(POEM, &members[0].data[..]);
+            assert_eq!(RUST, &members[1].data[..]);
+        }
+    }
+
+    #[test]
+    fn zip_members() {
+        let members = members(include_bytes!("../../tests/fixtures/bundle.zip").to_vec()).unwrap();
+        // docs/poem.txt 是 deflate 压缩的，rust.txt 没有压缩
+        assert_eq!(vec!["docs/poem.txt", "rust.txt"], paths(&members));
+        assert_eq!(POEM, &members[0].data[..]);
+        assert_eq!(RUST, &members[1].data[..]);
+    }
+
+    #[test]
+    fn corrupt_archives() {
+        let mut tar = include_bytes!("../../tests/fixtures/bundle.tar").to_vec();
+        tar[0] ^= 0xff;
+        assert!(members(tar).is_err());
+
+        let zip = include_bytes!("../../tests/fixtures/bundle.zip");
+        assert!(members(zip[..zip.len() - 30].to_vec()).is_err());
+        assert!(members(b"plain text".to_vec()).is_err());
+    }
+}
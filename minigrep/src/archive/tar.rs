@@ -0,0 +1,101 @@
+// tar 格式：每个文件是一个 512 字节的头部加上按 512 字节对齐的内容，最后是全 0 的块
+use super::Member;
+
+pub const BLOCK: usize = 512;
+
+// POSIX 的 "ustar\0" 和 GNU 的 "ustar  " 都以 ustar 开头
+pub fn is_tar(bytes: &[u8]) -> bool {
+    bytes.get(257..262) == Some(b"ustar")
+}
+
+// 头部中的字符串以 NUL 结尾，占满整个字段时没有 NUL
+fn text(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+// 数字一般是八进制文本；GNU tar 对放不下的大数字使用最高位为 1 的二进制（base-256）格式
+fn number(field: &[u8]) -> Result<u64, &'static str> {
+    if field.first().is_some_and(|&b| b & 0x80 != 0) {
+        return Ok(field[1..].iter().fold(0, |n, &b| (n << 8) | b as u64));
+    }
+
+    let digits = text(field);
+    let digits = digits.trim_matches(' ');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8).map_err(|_| "invalid number in tar header")
+}
+
+// 校验和是把校验和字段本身当作空格时，头部所有字节的和
+fn checksum_ok(header: &[u8]) -> Result<bool, &'static str> {
+    let expected = number(&header[148..156])?;
+    let sum: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u64 } else { b as u64 })
+        .sum();
+    Ok(sum == expected)
+}
+
+// pax 扩展头部由 "长度 键=值\n" 形式的记录组成，这里只关心 path
+fn pax_path(data: &[u8]) -> Option<String> {
+    let mut rest = data;
+    while !rest.is_empty() {
+        let space = rest.iter().position(|&b| b == b' ')?;
+        let len: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        let record = rest.get(space + 1..len)?;
+        if let Some(path) = record.strip_prefix(b"path=") {
+            return Some(String::from_utf8_lossy(path.strip_suffix(b"\n")?).into_owned());
+        }
+        rest = &rest[len..];
+    }
+    None
+}
+
+pub fn members(bytes: &[u8]) -> Result<Vec<Member>, &'static str> {
+    let mut members = Vec::new();
+    // GNU 长文件名（'L'）和 pax 扩展头部（'x'）给出的是下一个文件的路径
+    let mut next_path: Option<String> = None;
+    let mut pos = 0;
+
+    while let Some(header) = bytes.get(pos..pos + BLOCK) {
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        if !checksum_ok(header)? {
+            return Err("invalid tar header checksum");
+        }
+
+        let size = number(&header[124..136])? as usize;
+        let start = pos + BLOCK;
+        let data = start
+            .checked_add(size)
+            .and_then(|end| bytes.get(start..end))
+            .ok_or("truncated tar archive")?;
+        pos = start + size.div_ceil(BLOCK) * BLOCK;
+
+        match header[156] {
+            b'L' => next_path = Some(text(data)),
+            b'x' => next_path = pax_path(data).or(next_path),
+            // 普通文件，'7' 是很少见的连续文件，内容和普通文件一样
+            b'0' | b'\0' | b'7' => {
+                let path = next_path.take().unwrap_or_else(|| {
+                    let name = text(&header[0..100]);
+                    let prefix = if is_tar(header) { text(&header[345..500]) } else { String::new() };
+                    if prefix.is_empty() {
+                        name
+                    } else {
+                        format!("{}/{}", prefix, name)
+                    }
+                });
+                members.push(Member { path, data: data.to_vec() });
+            }
+            // 目录、链接、设备文件等没有可以搜索的内容
+            _ => next_path = None,
+        }
+    }
+
+    Ok(members)
+}
@@ -0,0 +1,92 @@
+// zip 格式：文件末尾的中央目录记录了每个文件的位置、大小和压缩方式
+use super::Member;
+use crate::decompress::{crc32, inflate, MAX_OUTPUT, TOO_LARGE};
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+
+// 空的 zip 文件只有中央目录的结束记录
+pub fn is_zip(bytes: &[u8]) -> bool {
+    matches!(u32_at(bytes, 0), Some(LOCAL_HEADER | END_OF_CENTRAL_DIRECTORY))
+}
+
+fn u16_at(bytes: &[u8], pos: usize) -> Option<usize> {
+    let b = bytes.get(pos..pos + 2)?;
+    Some(u16::from_le_bytes([b[0], b[1]]) as usize)
+}
+
+fn u32_at(bytes: &[u8], pos: usize) -> Option<u32> {
+    let b = bytes.get(pos..pos + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+// 结束记录在文件末尾，后面最多跟着 65535 字节的注释，所以从后往前找
+fn end_of_central_directory(bytes: &[u8]) -> Option<usize> {
+    let earliest = bytes.len().saturating_sub(22 + 65535);
+    (earliest..=bytes.len().checked_sub(22)?)
+        .rev()
+        .find(|&pos| u32_at(bytes, pos) == Some(END_OF_CENTRAL_DIRECTORY))
+}
+
+pub fn members(bytes: &[u8]) -> Result<Vec<Member>, &'static str> {
+    let corrupt = "corrupt zip archive";
+    let end = end_of_central_directory(bytes).ok_or(corrupt)?;
+    let count = u16_at(bytes, end + 10).ok_or(corrupt)?;
+    let mut pos = u32_at(bytes, end + 16).ok_or(corrupt)? as usize;
+    let mut members = Vec::new();
+    // 所有文件解压后的总大小也有上限，否则很多个小的压缩炸弹一样会耗尽内存
+    let mut remaining = MAX_OUTPUT;
+
+    for _ in 0..count {
+        if u32_at(bytes, pos) != Some(CENTRAL_HEADER) {
+            return Err(corrupt);
+        }
+        let flags = u16_at(bytes, pos + 8).ok_or(corrupt)?;
+        let method = u16_at(bytes, pos + 10).ok_or(corrupt)?;
+        let crc = u32_at(bytes, pos + 16).ok_or(corrupt)?;
+        let compressed = u32_at(bytes, pos + 20).ok_or(corrupt)?;
+        let size = u32_at(bytes, pos + 24).ok_or(corrupt)?;
+        let name_len = u16_at(bytes, pos + 28).ok_or(corrupt)?;
+        let extra_len = u16_at(bytes, pos + 30).ok_or(corrupt)?;
+        let comment_len = u16_at(bytes, pos + 32).ok_or(corrupt)?;
+        let local = u32_at(bytes, pos + 42).ok_or(corrupt)? as usize;
+        let name = bytes.get(pos + 46..pos + 46 + name_len).ok_or(corrupt)?;
+        let path = String::from_utf8_lossy(name).into_owned();
+        pos += 46 + name_len + extra_len + comment_len;
+
+        // 以 / 结尾的是目录
+        if path.ends_with('/') {
+            continue;
+        }
+        if flags & 1 != 0 {
+            return Err("encrypted zip archives are not supported");
+        }
+        if compressed == u32::MAX || size == u32::MAX {
+            return Err("zip64 archives are not supported");
+        }
+
+        // 本地头部中的文件名和扩展字段长度可能和中央目录中的不一样，要以本地头部为准
+        if u32_at(bytes, local) != Some(LOCAL_HEADER) {
+            return Err(corrupt);
+        }
+        let start = local
+            + 30
+            + u16_at(bytes, local + 26).ok_or(corrupt)?
+            + u16_at(bytes, local + 28).ok_or(corrupt)?;
+        let raw = bytes.get(start..start + compressed as usize).ok_or(corrupt)?;
+
+        let data = match method {
+            0 => raw.to_vec(),
+            8 => inflate(raw, remaining)?.0,
+            _ => return Err("unsupported zip compression method"),
+        };
+        if data.len() != size as usize || crc32(&data) != crc {
+            return Err("zip checksum mismatch");
+        }
+        remaining = remaining.checked_sub(data.len()).ok_or(TOO_LARGE)?;
+        members.push(Member { path, data });
+    }
+
+    Ok(members)
+}
@@ -1,13 +1,45 @@
 pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase(); // 将query转换为小写
+    let query = fold_case(query); // 将query转换为小写
     let mut results = Vec::new();
 
     for line in contents.lines() {
-        // 现在的query是String类型，因为to_lowercase()方法返回的是String类型
-        if line.to_lowercase().contains(&query) {
+        // 现在的query是String类型，因为fold_case()返回的是String类型
+        if fold_case(line).contains(&query) {
             results.push(line);
         }
     }
 
     results
-}
\ No newline at end of file
+}
+
+// 逐个字符转换成小写。str::to_lowercase 会根据上下文转换希腊字母 Σ（在词尾变成 ς），
+// 同一个查询单独转换和在一行中转换的结果可能不一样，导致本来包含查询的行匹配不上
+pub fn fold_case(text: &str) -> String {
+    text.chars().flat_map(fold_char).collect()
+}
+
+// 单个字符转换后的结果，可能不止一个字符（比如 İ 变成 i 加上一个组合用的点）。
+// 需要知道每个字符在原文中的位置时用它，和 fold_case 的结果一致
+pub fn fold_char(c: char) -> std::char::ToLowercase {
+    c.to_lowercase()
+}
+
+// 大小写的匹配模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMode {
+    Sensitive,
+    Insensitive,
+    // 查询中没有大写字母时忽略大小写，否则区分大小写
+    Smart,
+}
+
+impl CaseMode {
+    // 根据查询内容决定最终是否忽略大小写，所有的匹配方式都应该通过这里判断
+    pub fn ignore_case(self, query: &str) -> bool {
+        match self {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !query.chars().any(char::is_uppercase),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::MinigrepError;
+
+// 配置文件的位置：
+// 1. 环境变量 MINIGREP_CONFIG_PATH 指定的路径（文件必须存在）
+// 2. $XDG_CONFIG_HOME/minigrep/config
+// 3. ~/.config/minigrep/config
+// 第 2、3 种是默认位置，文件不存在时直接忽略
+pub fn config_path() -> Option<(PathBuf, bool)> {
+    if let Some(path) = env::var_os("MINIGREP_CONFIG_PATH") {
+        if !path.is_empty() {
+            return Some((PathBuf::from(path), true));
+        }
+    }
+
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some((base.join("minigrep").join("config"), false))
+}
+
+// 读取配置文件中的默认参数，没有配置文件时返回空列表
+pub fn load_default_args() -> Result<Vec<String>, MinigrepError> {
+    let (path, explicit) = match config_path() {
+        Some(found) => found,
+        None => return Ok(Vec::new()),
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(parse_args(&contents)),
+        Err(_) if !explicit && !path.exists() => Ok(Vec::new()),
+        Err(e) => Err(MinigrepError::io(&path)(e)),
+    }
+}
+
+// 配置文件每行一个参数，空行和以 # 开头的行会被忽略
+pub fn parse_args(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+// 严格解析布尔类型的环境变量，未设置时返回 None
+pub fn bool_env(name: &str) -> Result<Option<bool>, MinigrepError> {
+    match env::var(name) {
+        Ok(value) => parse_bool(&value)
+            .map(Some)
+            .map_err(|message| MinigrepError::Argument(format!("{}: {}", name, message))),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => Err(MinigrepError::Argument(format!("{} is not valid unicode", name))),
+    }
+}
+
+pub fn parse_bool(value: &str) -> Result<bool, &'static str> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err("boolean environment variables must be one of 1/0, true/false, yes/no, on/off"),
+    }
+}
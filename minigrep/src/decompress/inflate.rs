@@ -0,0 +1,283 @@
+// DEFLATE (RFC 1951) 解码器，参考 zlib 中 puff.c 的实现思路：
+// 用每种长度的码字数量 + 按码字排序的符号表来表示范式 Huffman 编码
+
+const MAX_BITS: usize = 15;
+
+// 解压结果超过调用者给出的上限
+pub const TOO_LARGE: &str = "decompressed data is too large";
+
+// 长度码 257..285 对应的基础长度和额外位数
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+    131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+// 距离码 0..29 对应的基础距离和额外位数
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+// 动态 Huffman 块中码长编码的码长出现顺序
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+// DEFLATE 的比特流是从每个字节的最低位开始读的
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit_buf: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data,
+            pos: 0,
+            bit_buf: 0,
+            bit_count: 0,
+        }
+    }
+
+    fn bits(&mut self, need: u32) -> Result<u32, &'static str> {
+        while self.bit_count < need {
+            let byte = *self.data.get(self.pos).ok_or("unexpected end of compressed data")?;
+            self.pos += 1;
+            self.bit_buf |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+
+        let value = self.bit_buf & ((1u32 << need) - 1);
+        self.bit_buf >>= need;
+        self.bit_count -= need;
+        Ok(value)
+    }
+
+    // 丢弃当前字节中剩余的位，用于 stored 块
+    fn align(&mut self) {
+        self.bit_buf = 0;
+        self.bit_count = 0;
+    }
+}
+
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, &'static str> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+
+        // 检查码长是否超额（允许不完整的编码，例如只有一个距离码的情况）
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left <<= 1;
+            left -= count as i32;
+            if left < 0 {
+                return Err("invalid huffman code lengths");
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, &'static str> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for len in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+
+        Err("invalid huffman code")
+    }
+}
+
+// 解压一段原始的 DEFLATE 数据，返回解压结果和消耗的字节数。
+// 结果超过 limit 字节时立即返回错误，不会先把整个结果放进内存
+pub fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize), &'static str> {
+    let mut reader = BitReader::new(data);
+    let mut out = Vec::new();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored(&mut reader, &mut out, limit)?,
+            1 => {
+                let (lit, dist) = fixed_tables()?;
+                codes(&mut reader, &mut out, &lit, &dist, limit)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic_tables(&mut reader)?;
+                codes(&mut reader, &mut out, &lit, &dist, limit)?;
+            }
+            _ => return Err("invalid deflate block type"),
+        }
+
+        if last {
+            break;
+        }
+    }
+
+    Ok((out, reader.pos))
+}
+
+fn stored(reader: &mut BitReader, out: &mut Vec<u8>, limit: usize) -> Result<(), &'static str> {
+    reader.align();
+
+    let header = reader
+        .data
+        .get(reader.pos..reader.pos + 4)
+        .ok_or("unexpected end of compressed data")?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    let nlen = u16::from_le_bytes([header[2], header[3]]);
+    if len != !nlen {
+        return Err("corrupt stored block length");
+    }
+    reader.pos += 4;
+
+    let block = reader
+        .data
+        .get(reader.pos..reader.pos + len as usize)
+        .ok_or("unexpected end of compressed data")?;
+    if out.len() + block.len() > limit {
+        return Err(TOO_LARGE);
+    }
+    out.extend_from_slice(block);
+    reader.pos += len as usize;
+
+    Ok(())
+}
+
+fn fixed_tables() -> Result<(Huffman, Huffman), &'static str> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5u8; 30])?))
+}
+
+fn dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman), &'static str> {
+    let nlen = reader.bits(5)? as usize + 257;
+    let ndist = reader.bits(5)? as usize + 1;
+    let ncode = reader.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err("too many length or distance codes");
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..ncode] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_table = Huffman::new(&code_lengths)?;
+
+    // 字面量/长度码和距离码的码长是连在一起编码的
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut index = 0;
+    while index < nlen + ndist {
+        let symbol = code_table.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..index].last().ok_or("repeat with no previous length")?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+
+        if index + repeat > nlen + ndist {
+            return Err("too many code lengths");
+        }
+        lengths[index..index + repeat].fill(value);
+        index += repeat;
+    }
+
+    if lengths[256] == 0 {
+        return Err("missing end-of-block code");
+    }
+
+    Ok((Huffman::new(&lengths[..nlen])?, Huffman::new(&lengths[nlen..])?))
+}
+
+fn codes(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    lit: &Huffman,
+    dist: &Huffman,
+    limit: usize,
+) -> Result<(), &'static str> {
+    loop {
+        let symbol = lit.decode(reader)? as usize;
+        match symbol {
+            0..=255 if out.len() >= limit => return Err(TOO_LARGE),
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let symbol = symbol - 257;
+                if symbol >= LENGTH_BASE.len() {
+                    return Err("invalid length code");
+                }
+                let len = LENGTH_BASE[symbol] as usize
+                    + reader.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+
+                let symbol = dist.decode(reader)? as usize;
+                if symbol >= DIST_BASE.len() {
+                    return Err("invalid distance code");
+                }
+                let distance = DIST_BASE[symbol] as usize
+                    + reader.bits(DIST_EXTRA[symbol] as u32)? as usize;
+                if distance > out.len() {
+                    return Err("distance too far back");
+                }
+                // 一个长度码最多复制 258 字节，压缩炸弹主要靠它放大，复制之前检查
+                if out.len() + len > limit {
+                    return Err(TOO_LARGE);
+                }
+
+                // 复制的区域可能和正在写入的区域重叠，所以只能逐字节复制
+                let start = out.len() - distance;
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
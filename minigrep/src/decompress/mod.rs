@@ -0,0 +1,200 @@
+mod inflate;
+pub use inflate::{inflate, TOO_LARGE};
+
+// 解压结果的大小上限。几 KB 的压缩炸弹就能解压出几 GB 的数据，不限制的话会耗尽内存
+pub const MAX_OUTPUT: usize = 1 << 30;
+
+// 通过文件开头的魔数识别压缩格式，而不是依赖扩展名
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+    Plain,
+}
+
+pub fn detect(bytes: &[u8]) -> Format {
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        Format::Gzip
+    } else if bytes.starts_with(b"BZh") {
+        Format::Bzip2
+    } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Format::Xz
+    } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Format::Zstd
+    } else {
+        Format::Plain
+    }
+}
+
+// 如果数据是压缩过的就解压，否则原样返回
+pub fn decompress(bytes: Vec<u8>) -> Result<Vec<u8>, &'static str> {
+    match detect(&bytes) {
+        Format::Gzip => gunzip(&bytes, MAX_OUTPUT),
+        Format::Bzip2 => Err("bzip2 compressed files are not supported yet"),
+        Format::Xz => Err("xz compressed files are not supported yet"),
+        Format::Zstd => Err("zstd compressed files are not supported yet"),
+        Format::Plain => Ok(bytes),
+    }
+}
+
+// gzip 文件头的标志位（RFC 1952）
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+// 解压 gzip 数据，`cat a.gz b.gz` 得到的多成员文件也能正确处理。
+// limit 是所有成员解压后的总大小上限
+pub fn gunzip(mut bytes: &[u8], limit: usize) -> Result<Vec<u8>, &'static str> {
+    let mut out = Vec::new();
+
+    while !bytes.is_empty() {
+        let body = skip_header(bytes)?;
+        let (data, used) = inflate::inflate(&bytes[body..], limit - out.len())?;
+
+        let trailer = bytes
+            .get(body + used..body + used + 8)
+            .ok_or("truncated gzip trailer")?;
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+        if crc != crc32(&data) || size != data.len() as u32 {
+            return Err("gzip checksum mismatch");
+        }
+
+        out.extend_from_slice(&data);
+        bytes = &bytes[body + used + 8..];
+    }
+
+    Ok(out)
+}
+
+// 返回压缩数据开始的位置
+fn skip_header(bytes: &[u8]) -> Result<usize, &'static str> {
+    if bytes.len() < 10 || bytes[0] != 0x1f || bytes[1] != 0x8b {
+        return Err("invalid gzip header");
+    }
+    if bytes[2] != 8 {
+        return Err("unknown gzip compression method");
+    }
+
+    let flags = bytes[3];
+    let mut pos = 10;
+
+    if flags & FEXTRA != 0 {
+        let len = bytes.get(pos..pos + 2).ok_or("invalid gzip header")?;
+        pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = bytes
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or("invalid gzip header")?;
+            pos += end + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+
+    if pos > bytes.len() {
+        return Err("invalid gzip header");
+    }
+    Ok(pos)
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POEM: &str = include_str!("../../poem.txt");
+
+    #[test]
+    fn gunzip_dynamic_huffman() {
+        let bytes = include_bytes!("../../tests/fixtures/poem.txt.gz").to_vec();
+        assert_eq!(Format::Gzip, detect(&bytes));
+        assert_eq!(POEM.as_bytes(), decompress(bytes).unwrap());
+    }
+
+    #[test]
+    fn gunzip_stored_and_fixed_blocks() {
+        let bytes = include_bytes!("../../tests/fixtures/stored.txt.gz").to_vec();
+        assert_eq!(POEM.as_bytes(), decompress(bytes).unwrap());
+
+        let bytes = include_bytes!("../../tests/fixtures/short.txt.gz").to_vec();
+        assert_eq!(b"Rust:\nsafe, fast, productive.\n".to_vec(), decompress(bytes).unwrap());
+    }
+
+    #[test]
+    fn plain_and_unsupported() {
+        assert_eq!(b"plain".to_vec(), decompress(b"plain".to_vec()).unwrap());
+        assert!(decompress(b"BZh91AY&SY".to_vec()).is_err());
+
+        let mut corrupt = include_bytes!("../../tests/fixtures/poem.txt.gz").to_vec();
+        let last = corrupt.len() - 5;
+        corrupt[last] ^= 0xff;
+        assert!(decompress(corrupt).is_err());
+    }
+
+    #[test]
+    fn output_size_limit() {
+        // 动态 Huffman、stored 和固定 Huffman 块都要检查，多成员文件按总大小计算
+        for bytes in [
+            &include_bytes!("../../tests/fixtures/poem.txt.gz")[..],
+            include_bytes!("../../tests/fixtures/stored.txt.gz"),
+        ] {
+            assert_eq!(POEM.as_bytes(), gunzip(bytes, POEM.len()).unwrap());
+            assert_eq!(Err(TOO_LARGE), gunzip(bytes, POEM.len() - 1));
+        }
+        let short = include_bytes!("../../tests/fixtures/short.txt.gz");
+        assert_eq!(Err(TOO_LARGE), gunzip(short, 10));
+        assert_eq!(Err(TOO_LARGE), gunzip(&[&short[..], short].concat(), 40));
+
+        // 全是 0 的数据每 258 字节只需要 13 位，解压到上限就停下，不会把整个结果放进内存
+        let (bomb, size) = gzip_zeros(4096);
+        assert!(bomb.len() < 8 * 1024 && size > 1 << 20);
+        assert_eq!(size, gunzip(&bomb, size).unwrap().len());
+        assert_eq!(Err(TOO_LARGE), gunzip(&bomb, size - 1));
+    }
+
+    // 用固定 Huffman 块压缩一个字面量 0 和 copies 个距离为 1、长度为 258 的复制，返回 gzip 数据和解压后的大小
+    fn gzip_zeros(copies: usize) -> (Vec<u8>, usize) {
+        let mut bits = Vec::new();
+        // 块头和额外位从低位开始写，Huffman 码字从高位开始写
+        let mut push = |value: u32, len: u32, code: bool| {
+            for i in 0..len {
+                let shift = if code { len - 1 - i } else { i };
+                bits.push((value >> shift) & 1);
+            }
+        };
+        push(1, 1, false); // 最后一个块
+        push(1, 2, false); // 固定 Huffman
+        push(0b0011_0000, 8, true); // 字面量 0
+        for _ in 0..copies {
+            push(0b1100_0101, 8, true); // 长度码 285：258 字节
+            push(0, 5, true); // 距离码 0：距离 1
+        }
+        push(0, 7, true); // 块结束
+
+        let size = 1 + 258 * copies;
+        let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+        out.extend(bits.chunks(8).map(|byte| byte.iter().rev().fold(0u8, |acc, &bit| (acc << 1) | bit as u8)));
+        out.extend(crc32(&vec![0; size]).to_le_bytes());
+        out.extend((size as u32).to_le_bytes());
+        (out, size)
+    }
+}
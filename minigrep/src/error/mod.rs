@@ -0,0 +1,111 @@
+// minigrep 的错误类型，调用者可以根据不同的情况分别处理，
+// 比如找不到文件和参数写错了应该给出不同的提示
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum MinigrepError {
+    // 命令行参数、配置文件或环境变量有问题
+    Argument(String),
+    // 读写文件出错，path 为 None 时是标准输出、终端等没有路径的地方
+    Io { path: Option<String>, source: io::Error },
+    // 文件内容无法解码，比如压缩数据或者归档文件已经损坏
+    Encoding { path: String, reason: String },
+    // 查询本身不合法，比如模糊查询太长
+    Pattern(String),
+    // --pre 的命令出错了。无法启动命令时 path 为 None，每个文件都会一样失败，所以不再继续；
+    // 命令处理某个文件失败时 path 是这个文件，只跳过这个文件
+    Preprocessor { command: String, path: Option<String>, reason: String },
+}
+
+impl MinigrepError {
+    // 给 I/O 错误加上出错的路径：`File::open(path).map_err(MinigrepError::io(path))`。
+    // 错误信息只用来显示，不是 UTF-8 的路径中无效的字节会被替换掉
+    pub fn io<P: AsRef<Path> + ?Sized>(path: &P) -> impl Fn(io::Error) -> MinigrepError + '_ {
+        move |source| MinigrepError::Io {
+            path: Some(path.as_ref().display().to_string()),
+            source,
+        }
+    }
+
+    // 出错的文件，只影响这一个文件的错误可以跳过这个文件继续搜索其它文件
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            MinigrepError::Io { path, .. } => path.as_deref(),
+            MinigrepError::Encoding { path, .. } => Some(path),
+            MinigrepError::Preprocessor { path, .. } => path.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn encoding<P: AsRef<Path> + ?Sized>(path: &P) -> impl Fn(&str) -> MinigrepError + '_ {
+        move |reason| MinigrepError::Encoding {
+            path: path.as_ref().display().to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+// 只显示这一层的信息，底层的原因通过 source() 获取，完整的信息可以用 display_chain 得到
+impl fmt::Display for MinigrepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinigrepError::Argument(message) => write!(f, "{}", message),
+            MinigrepError::Io { path: Some(path), .. } => write!(f, "{}", path),
+            MinigrepError::Io { path: None, .. } => write!(f, "I/O error"),
+            MinigrepError::Encoding { path, reason } => write!(f, "{}: {}", path, reason),
+            MinigrepError::Pattern(message) => write!(f, "invalid pattern: {}", message),
+            MinigrepError::Preprocessor { command, path: Some(path), reason } => {
+                write!(f, "{}: preprocessor {} failed: {}", path, command, reason)
+            }
+            MinigrepError::Preprocessor { command, path: None, reason } => {
+                write!(f, "cannot run preprocessor {}: {}", command, reason)
+            }
+        }
+    }
+}
+
+impl Error for MinigrepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MinigrepError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MinigrepError {
+    fn from(source: io::Error) -> MinigrepError {
+        MinigrepError::Io { path: None, source }
+    }
+}
+
+// 把错误和它所有的 source 用 ": " 连起来，比如 "poem.txt: No such file or directory (os error 2)"
+pub fn display_chain(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_includes_path_and_cause() {
+        let error = MinigrepError::io("poem.txt")(io::Error::new(io::ErrorKind::NotFound, "not found"));
+        assert!(matches!(&error, MinigrepError::Io { source, .. } if source.kind() == io::ErrorKind::NotFound));
+        assert_eq!("poem.txt: not found", display_chain(&error));
+
+        let error = MinigrepError::from(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"));
+        assert_eq!("I/O error: broken pipe", display_chain(&error));
+        assert!(MinigrepError::Pattern("too long".to_string()).source().is_none());
+    }
+}
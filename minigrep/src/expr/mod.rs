@@ -0,0 +1,287 @@
+// --expr 的布尔查询：`ERROR & !timeout`、`(foo | bar) & baz`。
+// 优先级从高到低是 !、&、|，每个词用普通的匹配方式（或者模糊匹配）判断这一行是否包含它。
+// 词中有空格或运算符时可以用双引号括起来，引号中的 \" 和 \\ 表示双引号和反斜杠
+use crate::MinigrepError;
+
+// ! 和括号最多嵌套的层数。eval 和 try_map 是递归的，限制了嵌套之后树的深度也有了上限，不会栈溢出
+const MAX_DEPTH: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr<T> {
+    Term(T),
+    Not(Box<Expr<T>>),
+    And(Box<Expr<T>>, Box<Expr<T>>),
+    Or(Box<Expr<T>>, Box<Expr<T>>),
+}
+
+impl<T> Expr<T> {
+    // 把每个词换成别的类型，比如从字符串换成匹配器
+    pub fn try_map<U, E>(self, f: &mut impl FnMut(T) -> Result<U, E>) -> Result<Expr<U>, E> {
+        Ok(match self {
+            Expr::Term(term) => Expr::Term(f(term)?),
+            Expr::Not(inner) => Expr::Not(Box::new(inner.try_map(f)?)),
+            Expr::And(left, right) => Expr::And(Box::new(left.try_map(f)?), Box::new(right.try_map(f)?)),
+            Expr::Or(left, right) => Expr::Or(Box::new(left.try_map(f)?), Box::new(right.try_map(f)?)),
+        })
+    }
+
+    // & 和 | 都是短路求值，左边能决定结果时不再匹配右边的词
+    pub fn eval(&self, matches: &impl Fn(&T) -> bool) -> bool {
+        match self {
+            Expr::Term(term) => matches(term),
+            Expr::Not(inner) => !inner.eval(matches),
+            Expr::And(left, right) => left.eval(matches) && right.eval(matches),
+            Expr::Or(left, right) => left.eval(matches) || right.eval(matches),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Term(String),
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+    End,
+}
+
+// 每个记号和它在表达式中的列号（从 1 开始，按字符计算）
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    // 当前在几层 ! 和括号里面
+    depth: usize,
+}
+
+pub fn parse(input: &str) -> Result<Expr<String>, MinigrepError> {
+    let mut parser = Parser {
+        input,
+        tokens: tokenize(input)?,
+        pos: 0,
+        depth: 0,
+    };
+    let expr = parser.or()?;
+    match parser.peek() {
+        Token::End => Ok(expr),
+        Token::Close => Err(parser.error("unmatched ')'")),
+        _ => Err(parser.error("expected '&' or '|'")),
+    }
+}
+
+// 错误信息中带上表达式，并在出错的位置下面画一个 ^
+fn error_at(input: &str, column: usize, message: &str) -> MinigrepError {
+    MinigrepError::Pattern(format!(
+        "{} at column {}\n  {}\n  {}^",
+        message,
+        column,
+        input,
+        " ".repeat(column - 1)
+    ))
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, MinigrepError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().enumerate().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let column = i + 1;
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '!' => Token::Not,
+            '&' => Token::And,
+            '|' => Token::Or,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '"' => {
+                let mut term = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => term.push(c),
+                            None => return Err(error_at(input, column, "unterminated quote")),
+                        },
+                        Some((_, c)) => term.push(c),
+                        None => return Err(error_at(input, column, "unterminated quote")),
+                    }
+                }
+                Token::Term(term)
+            }
+            // 没有引号的词到空白或者运算符为止，! 只有在词的开头才是运算符，所以 `hello!` 是一个词
+            c => {
+                let mut term = c.to_string();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() || "&|()\"".contains(c) {
+                        break;
+                    }
+                    term.push(c);
+                    chars.next();
+                }
+                Token::Term(term)
+            }
+        };
+        tokens.push((token, column));
+    }
+
+    tokens.push((Token::End, input.chars().count() + 1));
+    Ok(tokens)
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: &str) -> MinigrepError {
+        error_at(self.input, self.tokens[self.pos].1, message)
+    }
+
+    fn or(&mut self) -> Result<Expr<String>, MinigrepError> {
+        let mut items = vec![self.and()?];
+        while *self.peek() == Token::Or {
+            self.next();
+            items.push(self.and()?);
+        }
+        Ok(balanced(items, Expr::Or))
+    }
+
+    fn and(&mut self) -> Result<Expr<String>, MinigrepError> {
+        let mut items = vec![self.unary()?];
+        while *self.peek() == Token::And {
+            self.next();
+            items.push(self.unary()?);
+        }
+        Ok(balanced(items, Expr::And))
+    }
+
+    // 进入一层 ! 或者括号，超过 MAX_DEPTH 时报错，错误指向超出的那一层
+    fn nest(&mut self) -> Result<(), MinigrepError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(&format!("expression nested more than {} levels deep", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        self.next();
+        Ok(())
+    }
+
+    fn unary(&mut self) -> Result<Expr<String>, MinigrepError> {
+        match self.peek() {
+            Token::Not => {
+                self.nest()?;
+                let inner = self.unary()?;
+                self.depth -= 1;
+                Ok(Expr::Not(Box::new(inner)))
+            }
+            Token::Open => {
+                self.nest()?;
+                let inner = self.or()?;
+                if *self.peek() != Token::Close {
+                    return Err(self.error("expected ')'"));
+                }
+                self.next();
+                self.depth -= 1;
+                Ok(inner)
+            }
+            Token::Term(_) => match self.next() {
+                Token::Term(term) => Ok(Expr::Term(term)),
+                _ => unreachable!(),
+            },
+            _ => Err(self.error("expected a term, '!' or '('")),
+        }
+    }
+}
+
+// 把 a | b | c | d 组合成 (a | b) | (c | d)，而不是一直向左嵌套，很长的一串词也只有对数的深度。
+// & 和 | 满足结合律，短路求值时仍然是从左到右判断每个词
+fn balanced<T>(mut items: Vec<Expr<T>>, op: impl Fn(Box<Expr<T>>, Box<Expr<T>>) -> Expr<T>) -> Expr<T> {
+    while items.len() > 1 {
+        let mut pairs = items.into_iter();
+        let mut next = Vec::new();
+        while let Some(left) = pairs.next() {
+            next.push(match pairs.next() {
+                Some(right) => op(Box::new(left), Box::new(right)),
+                None => left,
+            });
+        }
+        items = next;
+    }
+    items.pop().expect("at least one item")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(text: &str) -> Box<Expr<String>> {
+        Box::new(Expr::Term(text.to_string()))
+    }
+
+    fn error(input: &str) -> String {
+        match parse(input) {
+            Err(MinigrepError::Pattern(message)) => message,
+            other => panic!("expected a pattern error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn precedence_and_quotes() {
+        assert_eq!(
+            Expr::Or(term("a"), Box::new(Expr::And(term("b"), Box::new(Expr::Not(term("c")))))),
+            parse("a | b & !c").unwrap()
+        );
+        assert_eq!(
+            Expr::And(Box::new(Expr::Or(term("a"), term("b"))), term("time out!")),
+            parse(r#"(a|b)&"time out!""#).unwrap()
+        );
+        assert_eq!(Expr::Term("hello!".to_string()), parse("hello!").unwrap());
+        assert_eq!(Expr::Term(r#"say "hi""#.to_string()), parse(r#""say \"hi\"""#).unwrap());
+
+        let expr = parse("ERROR & !timeout").unwrap();
+        let matches = |line: &str| expr.eval(&|term: &String| line.contains(term.as_str()));
+        assert!(matches("ERROR disk full"));
+        assert!(!matches("ERROR timeout"));
+        assert!(!matches("INFO ok"));
+    }
+
+    #[test]
+    fn errors_point_at_column() {
+        assert_eq!("expected a term, '!' or '(' at column 9\n  ERROR & \n          ^", error("ERROR & "));
+        assert_eq!("expected ')' at column 7\n  (a | b\n        ^", error("(a | b"));
+        assert_eq!("unmatched ')' at column 2\n  a)\n   ^", error("a)"));
+        assert_eq!("expected '&' or '|' at column 3\n  a b\n    ^", error("a b"));
+        assert_eq!("unterminated quote at column 5\n  a & \"b\n      ^", error("a & \"b"));
+        assert!(error("").starts_with("expected a term"));
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |open: &str, close: &str, n: usize| format!("{}a{}", open.repeat(n), close.repeat(n));
+        let matches = |expr: &Expr<String>| expr.eval(&|term: &String| term == "a");
+
+        assert!(!matches(&parse(&nested("!", "", MAX_DEPTH - 1)).unwrap()));
+        assert!(matches(&parse(&nested("(", ")", MAX_DEPTH)).unwrap()));
+        assert!(matches(&parse(&nested("!(!", ")", MAX_DEPTH / 3)).unwrap()));
+
+        let message = error(&nested("(", ")", MAX_DEPTH + 1));
+        assert!(message.starts_with("expression nested more than 100 levels deep at column 101\n"));
+        assert!(error(&nested("!", "", 100_000)).starts_with("expression nested more than 100 levels deep"));
+
+        // 很长的一串 | 和 & 不受限制
+        let chain = vec!["b"; 100_000].join(" | ") + " | a & " + &vec!["a"; 100_000].join(" & ");
+        let expr = parse(&chain).unwrap();
+        assert!(matches(&expr));
+        let lengths = expr.try_map(&mut |term: String| Ok::<_, ()>(term.len())).unwrap();
+        assert!(lengths.eval(&|&len| len == 1));
+    }
+}
@@ -0,0 +1,87 @@
+// --field：只在 CSV/TSV 的某一列中匹配查询，输出时仍然是整行。
+// 按字节处理，所以分隔符只能是 ASCII 字符；引号中的换行符不支持，每一行单独解析
+use std::borrow::Cow;
+
+// 取出第 n 列（从 1 开始），没有这一列时返回 None。
+// 用双引号括起来的列中可以包含分隔符，两个连续的双引号表示一个双引号字符
+pub fn field(line: &[u8], delimiter: u8, n: usize) -> Option<Cow<'_, [u8]>> {
+    let mut rest = line;
+    for _ in 1..n {
+        rest = &rest[field_end(rest, delimiter)?..];
+    }
+    Some(unquote(&rest[..field_len(rest, delimiter)]))
+}
+
+// 第一列的长度，不包括后面的分隔符
+fn field_len(rest: &[u8], delimiter: u8) -> usize {
+    let mut quoted = rest.first() == Some(&b'"');
+    let mut i = usize::from(quoted);
+    while i < rest.len() {
+        match rest[i] {
+            b'"' if quoted && rest.get(i + 1) == Some(&b'"') => i += 1,
+            b'"' if quoted => quoted = false,
+            b if b == delimiter && !quoted => return i,
+            _ => {}
+        }
+        i += 1;
+    }
+    rest.len()
+}
+
+// 下一列开始的位置，已经是最后一列时返回 None
+fn field_end(rest: &[u8], delimiter: u8) -> Option<usize> {
+    let len = field_len(rest, delimiter);
+    (len < rest.len()).then_some(len + 1)
+}
+
+// 去掉两边的引号，把 "" 换回 "。引号不完整的列原样返回
+fn unquote(field: &[u8]) -> Cow<'_, [u8]> {
+    match field.strip_prefix(b"\"").and_then(|inner| inner.strip_suffix(b"\"")) {
+        Some(inner) if inner.windows(2).any(|w| w == b"\"\"") => {
+            let mut out = Vec::with_capacity(inner.len());
+            let mut i = 0;
+            while i < inner.len() {
+                out.push(inner[i]);
+                i += if inner[i] == b'"' && inner.get(i + 1) == Some(&b'"') { 2 } else { 1 };
+            }
+            Cow::Owned(out)
+        }
+        Some(inner) => Cow::Borrowed(inner),
+        None => Cow::Borrowed(field),
+    }
+}
+
+// 命令行中不方便直接输入制表符，所以也接受 \t 和 tab
+pub fn parse_delimiter(value: &str) -> Option<u8> {
+    match value {
+        "\\t" | "tab" => Some(b'\t'),
+        _ if value.len() == 1 && value.is_ascii() && value != "\"" => Some(value.as_bytes()[0]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(line: &str, delimiter: u8, n: usize) -> Option<String> {
+        field(line.as_bytes(), delimiter, n).map(|field| String::from_utf8(field.into_owned()).unwrap())
+    }
+
+    #[test]
+    fn quoted_csv_fields() {
+        let line = r#"2024-01-02,"timeout, retrying","said ""hi""",,last"#;
+        assert_eq!(Some("2024-01-02".to_string()), get(line, b',', 1));
+        assert_eq!(Some("timeout, retrying".to_string()), get(line, b',', 2));
+        assert_eq!(Some(r#"said "hi""#.to_string()), get(line, b',', 3));
+        assert_eq!(Some(String::new()), get(line, b',', 4));
+        assert_eq!(Some("last".to_string()), get(line, b',', 5));
+        assert_eq!(None, get(line, b',', 6));
+
+        assert_eq!(Some("b c".to_string()), get("a\tb c\td", b'\t', 2));
+        // 引号没有闭合时剩下的内容都属于这一列
+        assert_eq!(Some(r#""open, still"#.to_string()), get(r#"x,"open, still"#, b',', 2));
+        assert_eq!(Some(b'\t'), parse_delimiter("\\t"));
+        assert_eq!(None, parse_delimiter("::"));
+    }
+}
@@ -0,0 +1,267 @@
+// 跟随模式：类似 `tail -f | grep`，搜索完已有内容后继续读取追加的数据
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::{
+    line_match, line_matcher, match_limit, outcome, report, walk_paths, warn, Config, MinigrepError, Outcome,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// 用设备号和 inode 判断路径指向的是不是同一个文件，日志轮转后 inode 会变化
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+struct Follower {
+    path: PathBuf,
+    file: Option<File>,
+    id: Option<(u64, u64)>,
+    // 已经读到的位置
+    pos: u64,
+    // 最后一行还没有读到换行符时先存起来，等读到完整的一行再处理
+    partial: Vec<u8>,
+    line_number: usize,
+    // 已经输出的匹配行数，达到 -m 的限制后不再跟随这个文件
+    matched: usize,
+    // 上一次读取出错了，同样的问题不重复报告，恢复之后再出错才报告
+    failing: bool,
+}
+
+impl Follower {
+    fn new(path: &Path) -> Follower {
+        Follower {
+            path: path.to_path_buf(),
+            file: None,
+            id: None,
+            pos: 0,
+            partial: Vec::new(),
+            line_number: 0,
+            matched: 0,
+            failing: false,
+        }
+    }
+
+    // 和 poll 一样，但是错误只在第一次出现时用 warn 报告，出错时返回空的结果
+    fn poll_reported(&mut self, config: &Config, messages: &mut impl Write) -> Vec<(usize, String)> {
+        match self.poll() {
+            Ok(lines) => {
+                self.failing = false;
+                lines
+            }
+            Err(e) => {
+                if !self.failing {
+                    warn(config, &MinigrepError::io(&self.path)(e), messages);
+                    self.failing = true;
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    // 读取新增的完整行，返回 (行号, 内容)
+    fn poll(&mut self) -> io::Result<Vec<(usize, String)>> {
+        let mut lines = Vec::new();
+
+        match fs::metadata(&self.path) {
+            Ok(metadata) => {
+                let id = file_id(&metadata);
+                if self.file.is_some() && id != self.id {
+                    // 文件被轮转了：先把旧文件中剩下的内容读完，再从头读新文件
+                    self.read_lines(&mut lines)?;
+                    self.flush_partial(&mut lines);
+                    self.file = None;
+                }
+
+                if self.file.is_none() {
+                    self.file = Some(File::open(&self.path)?);
+                    self.id = id;
+                    self.reset();
+                } else if metadata.len() < self.pos {
+                    // 文件被截断了，从头开始读
+                    self.reset();
+                }
+            }
+            // 文件暂时不存在（比如轮转的中间状态），继续读旧的文件
+            Err(e) if e.kind() == io::ErrorKind::NotFound && self.file.is_some() => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(lines),
+            Err(e) => return Err(e),
+        }
+
+        self.read_lines(&mut lines)?;
+        Ok(lines)
+    }
+
+    fn reset(&mut self) {
+        self.pos = 0;
+        self.partial.clear();
+        self.line_number = 0;
+    }
+
+    fn read_lines(&mut self, lines: &mut Vec<(usize, String)>) -> io::Result<()> {
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+
+        file.seek(SeekFrom::Start(self.pos))?;
+        let mut buf = Vec::new();
+        self.pos += file.read_to_end(&mut buf)? as u64;
+        self.partial.extend_from_slice(&buf);
+
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            self.push_line(&line[..end], lines);
+        }
+        Ok(())
+    }
+
+    // 旧文件最后一行可能没有换行符，切换文件之前也要处理
+    fn flush_partial(&mut self, lines: &mut Vec<(usize, String)>) {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.push_line(&line, lines);
+        }
+    }
+
+    fn push_line(&mut self, line: &[u8], lines: &mut Vec<(usize, String)>) {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        self.line_number += 1;
+        lines.push((self.line_number, String::from_utf8_lossy(line).into_owned()));
+    }
+}
+
+pub fn run(config: &Config) -> Result<Outcome, MinigrepError> {
+    let ignore_case = config.case_mode.ignore_case(&config.query);
+    let matcher = line_matcher(config, &config.query, ignore_case)?;
+    let mut messages = io::stderr();
+    let mut errors = Vec::new();
+    let mut followers: Vec<Follower> = walk_paths(&config.file_paths, &mut errors)
+        .iter()
+        .map(|path| Follower::new(path))
+        .collect();
+    // 读不了的目录报告一次后跳过，其中的文件不会出现
+    let mut had_errors = !errors.is_empty();
+    for error in &errors {
+        warn(config, error, &mut messages);
+    }
+
+    let limit = match_limit(config);
+    let stdout = io::stdout();
+    loop {
+        for follower in &mut followers {
+            if follower.matched >= limit {
+                continue;
+            }
+            let lines = follower.poll_reported(config, &mut messages);
+            had_errors |= follower.failing;
+
+            let hits = lines
+                .into_iter()
+                .filter_map(|(number, line)| {
+                    let found = line_match(config, &matcher, line.as_bytes())?;
+                    Some(Ok((number, line, found.span)))
+                })
+                .take(limit - follower.matched);
+            let mut out = stdout.lock();
+            follower.matched += report(config, &follower.path, hits, &mut out)?;
+            out.flush()?;
+        }
+
+        // 所有文件都达到了 -m 的限制（-q 的限制是 1）就结束，和 grep -m 一样
+        if followers.iter().all(|follower| follower.matched >= limit) {
+            return Ok(outcome(config, true, had_errors));
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Temp;
+    use std::ffi::OsStr;
+    use std::fs::OpenOptions;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = OpenOptions::new().append(true).create(true).open(path).unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    fn texts(lines: Vec<(usize, String)>) -> Vec<String> {
+        lines.into_iter().map(|(number, line)| format!("{}:{}", number, line)).collect()
+    }
+
+    #[test]
+    fn follows_appends_truncation_and_rotation() {
+        let dir = Temp::dir("follow");
+        let path = dir.join("app.log");
+        fs::write(&path, "one\ntwo\nthr").unwrap();
+
+        let mut follower = Follower::new(&path);
+        assert_eq!(vec!["1:one", "2:two"], texts(follower.poll().unwrap()));
+        assert!(follower.poll().unwrap().is_empty());
+
+        // 补全了上一次没有换行符的行
+        append(&path, "ee\nfour\n");
+        assert_eq!(vec!["3:three", "4:four"], texts(follower.poll().unwrap()));
+
+        // 截断后从头开始
+        fs::write(&path, "new\n").unwrap();
+        assert_eq!(vec!["1:new"], texts(follower.poll().unwrap()));
+
+        // 轮转：旧文件被改名，然后创建新文件，旧文件最后写入的内容不能丢
+        append(&path, "last");
+        let rotated = dir.join("app.log.1");
+        fs::rename(&path, &rotated).unwrap();
+        fs::write(&path, "fresh\n").unwrap();
+        assert_eq!(vec!["2:last", "1:fresh"], texts(follower.poll().unwrap()));
+    }
+
+    #[test]
+    fn errors_are_reported_once() {
+        let dir = Temp::dir("follow-errors");
+        let parent = dir.join("logs");
+        let path = parent.join("app.log");
+        let args = [OsStr::new("minigrep"), OsStr::new("back"), path.as_os_str()];
+        let config = Config::build_from(&args, &[], None).unwrap();
+        let mut follower = Follower::new(&path);
+        let mut err = Vec::new();
+
+        // 上一级路径是普通文件，每次读取都会失败，但只报告一次
+        fs::write(&parent, "").unwrap();
+        assert!(follower.poll_reported(&config, &mut err).is_empty());
+        assert!(follower.poll_reported(&config, &mut err).is_empty());
+        let messages = String::from_utf8_lossy(&err).into_owned();
+        assert_eq!(1, messages.lines().count());
+        assert!(messages.starts_with(&format!("minigrep: {}: ", path.display())));
+
+        // 恢复之后再出错要重新报告
+        fs::remove_file(&parent).unwrap();
+        fs::create_dir(&parent).unwrap();
+        fs::write(&path, "back\n").unwrap();
+        assert_eq!(vec!["1:back"], texts(follower.poll_reported(&config, &mut err)));
+        fs::remove_dir_all(&parent).unwrap();
+        fs::write(&parent, "").unwrap();
+        assert!(follower.poll_reported(&config, &mut err).is_empty());
+        assert_eq!(2, String::from_utf8_lossy(&err).lines().count());
+
+        // --no-messages 时不输出，但仍然记下出错了
+        let quiet = Config { no_messages: true, ..config };
+        let mut follower = Follower::new(&path);
+        let mut err = Vec::new();
+        assert!(follower.poll_reported(&quiet, &mut err).is_empty());
+        assert!(err.is_empty() && follower.failing);
+    }
+}
@@ -0,0 +1,233 @@
+// 模糊匹配：找出和查询的编辑距离（Levenshtein 距离）不超过 N 的子串
+// 使用 Myers 的位并行算法，每读入一个字符只需要常数次位运算，所以查询最长 64 个字符
+use std::collections::HashMap;
+
+use crate::case_insensitive::{fold_case, fold_char};
+use crate::MinigrepError;
+
+pub const MAX_QUERY_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    // 匹配到的子串在这一行中的字节偏移 [start, end)
+    pub start: usize,
+    pub end: usize,
+    pub distance: usize,
+}
+
+pub struct FuzzyMatcher {
+    // 每个字符在查询中出现的位置组成的位掩码
+    forward: HashMap<char, u64>,
+    // 反转后的查询的位掩码，用来从匹配的结尾往回找开头
+    backward: HashMap<char, u64>,
+    len: usize,
+    max_distance: usize,
+    ignore_case: bool,
+}
+
+impl FuzzyMatcher {
+    pub fn new(query: &str, max_distance: usize, ignore_case: bool) -> Result<FuzzyMatcher, MinigrepError> {
+        // 和字面匹配一样转换大小写，一个字符可能变成多个字符
+        let query: Vec<char> = if ignore_case {
+            fold_case(query).chars().collect()
+        } else {
+            query.chars().collect()
+        };
+        if query.len() > MAX_QUERY_LEN {
+            return Err(MinigrepError::Pattern(format!(
+                "fuzzy queries can be at most {} characters long",
+                MAX_QUERY_LEN
+            )));
+        }
+
+        let mut forward = HashMap::new();
+        let mut backward = HashMap::new();
+        for (i, &c) in query.iter().enumerate() {
+            *forward.entry(c).or_insert(0) |= 1u64 << i;
+            *backward.entry(c).or_insert(0) |= 1u64 << (query.len() - 1 - i);
+        }
+
+        Ok(FuzzyMatcher {
+            forward,
+            backward,
+            len: query.len(),
+            max_distance,
+            ignore_case,
+        })
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        self.find(line).is_some()
+    }
+
+    // 返回这一行中编辑距离最小的匹配，距离相同时取最靠前的那个
+    pub fn find(&self, line: &str) -> Option<FuzzyMatch> {
+        // 转换出来的每个字符都记下原来那个字符的字节偏移
+        let chars: Vec<(usize, char)> = if self.ignore_case {
+            line.char_indices().flat_map(|(i, c)| fold_char(c).map(move |folded| (i, folded))).collect()
+        } else {
+            line.char_indices().collect()
+        };
+
+        // 查询比允许的编辑距离还短时，空行也能匹配
+        if chars.is_empty() || self.len == 0 {
+            return (self.len <= self.max_distance).then_some(FuzzyMatch {
+                start: 0,
+                end: 0,
+                distance: self.len,
+            });
+        }
+
+        // 先正向扫描，找到距离最小的匹配结尾
+        let mut best: Option<(usize, usize)> = None;
+        myers(&self.forward, self.len, false, chars.iter().map(|&(_, c)| c), |j, score| {
+            if score <= self.max_distance && best.is_none_or(|(_, d)| score < d) {
+                best = Some((j, score));
+            }
+            score > 0
+        });
+        let (end, distance) = best?;
+
+        // 再从结尾往回扫描反转后的查询，这次匹配必须从结尾开始，
+        // 第一次达到这个距离的位置就是最短匹配的开头
+        let mut start = 0;
+        myers(&self.backward, self.len, true, chars[..=end].iter().rev().map(|&(_, c)| c), |j, score| {
+            if score == distance {
+                start = end - j;
+                return false;
+            }
+            true
+        });
+
+        let end_byte = chars[end].0 + line[chars[end].0..].chars().next().map_or(0, char::len_utf8);
+        Some(FuzzyMatch {
+            start: chars[start].0,
+            end: end_byte,
+            distance,
+        })
+    }
+}
+
+// Myers 算法：每读入一个字符，用 Pv/Mv 两个位向量表示动态规划表中这一列的增量，
+// `anchored` 为 true 时匹配必须从文本开头开始，否则可以从任意位置开始，
+// `visit(j, score)` 收到以第 j 个字符结尾的最小编辑距离，返回 false 时提前结束
+fn myers(
+    peq: &HashMap<char, u64>,
+    len: usize,
+    anchored: bool,
+    text: impl Iterator<Item = char>,
+    mut visit: impl FnMut(usize, usize) -> bool,
+) {
+    let mask = if len == 64 { !0 } else { (1u64 << len) - 1 };
+    let high = 1u64 << (len - 1);
+    let mut pv = mask;
+    let mut mv = 0u64;
+    let mut score = len;
+
+    for (j, c) in text.enumerate() {
+        let eq = peq.get(&c).copied().unwrap_or(0);
+        let xv = eq | mv;
+        let xh = (((eq & pv).wrapping_add(pv)) ^ pv) | eq;
+        let mut ph = mv | !(xh | pv);
+        let mut mh = pv & xh;
+
+        if ph & high != 0 {
+            score += 1;
+        } else if mh & high != 0 {
+            score -= 1;
+        }
+
+        // 不固定开头时第 0 行始终为 0，左移时补 0；固定开头时第 0 行逐列加 1，左移时补 1
+        ph = ((ph << 1) | anchored as u64) & mask;
+        mh = (mh << 1) & mask;
+        pv = (mh | !(xv | ph)) & mask;
+        mv = ph & xv;
+
+        if !visit(j, score) {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_typos() {
+        let matcher = FuzzyMatcher::new("productive", 1, false).unwrap();
+        assert_eq!(
+            Some(FuzzyMatch { start: 12, end: 21, distance: 1 }),
+            matcher.find("safe, fast, produtive.")
+        );
+        assert!(!matcher.is_match("Pick three."));
+
+        let matcher = FuzzyMatcher::new("nobody", 0, false).unwrap();
+        assert_eq!(Some(FuzzyMatch { start: 8, end: 14, distance: 0 }), matcher.find("Are you nobody, too?"));
+    }
+
+    #[test]
+    fn ignore_case_and_unicode() {
+        let matcher = FuzzyMatcher::new("DREARY", 2, true).unwrap();
+        assert_eq!(Some(FuzzyMatch { start: 4, end: 9, distance: 1 }), matcher.find("How drery to be somebody!"));
+
+        // İ 转换成小写是 i 加上组合用的点，两个字符都要保留，和 -i 的字面匹配一致
+        let matcher = FuzzyMatcher::new("İSTANBUL", 0, true).unwrap();
+        let line = "in i\u{307}stanbul";
+        let found = matcher.find(line).unwrap();
+        assert_eq!(("i\u{307}stanbul", 0), (&line[found.start..found.end], found.distance));
+        assert_eq!(Some(FuzzyMatch { start: 0, end: 9, distance: 0 }), matcher.find("İstanbul"));
+
+        let matcher = FuzzyMatcher::new("大人物", 1, false).unwrap();
+        let line = "因为这里属于没劲的大任物！";
+        let found = matcher.find(line).unwrap();
+        assert_eq!("大任物", &line[found.start..found.end]);
+    }
+
+    #[test]
+    fn matches_brute_force_distance() {
+        let lines = ["kitten sitting", "abcabcabd", "Rust: safe, fast", "xyz", ""];
+        for query in ["sitting", "abd", "fats", "zz"] {
+            for line in lines {
+                for k in 0..3 {
+                    let matcher = FuzzyMatcher::new(query, k, false).unwrap();
+                    let expected = best_substring_distance(query, line);
+                    match matcher.find(line) {
+                        Some(found) => {
+                            assert_eq!(expected, found.distance);
+                            assert_eq!(found.distance, levenshtein(query, &line[found.start..found.end]));
+                        }
+                        None => assert!(expected > k),
+                    }
+                }
+            }
+        }
+    }
+
+    fn levenshtein(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, ca) in a.chars().enumerate() {
+            let mut prev = row[0];
+            row[0] = i + 1;
+            for j in 0..b.len() {
+                let cur = row[j + 1];
+                row[j + 1] = (prev + (ca != b[j]) as usize).min(row[j] + 1).min(cur + 1);
+                prev = cur;
+            }
+        }
+        row[b.len()]
+    }
+
+    fn best_substring_distance(query: &str, line: &str) -> usize {
+        let chars: Vec<char> = line.chars().collect();
+        let mut best = query.chars().count();
+        for i in 0..=chars.len() {
+            for j in i..=chars.len() {
+                let sub: String = chars[i..j].iter().collect();
+                best = best.min(levenshtein(query, &sub));
+            }
+        }
+        best
+    }
+}
@@ -0,0 +1,503 @@
+// 三元组（trigram）索引：记录每个三元组（连续 3 个字节）出现在哪些文件中，
+// 搜索时先用查询的三元组排除不可能匹配的文件，剩下的文件再用普通的方式验证
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::multiline::unescape;
+use crate::{collect_files, Config, MinigrepError};
+
+// 索引文件保存在被索引的目录下，以 . 开头所以搜索目录时会被跳过
+pub const INDEX_FILE: &str = ".minigrep-index";
+
+// 索引文件的格式，整数都是小端序：
+//   MAGIC
+//   文件数 u32，然后每个文件：修改时间秒数 u64、纳秒 u32、大小 u64、路径长度 u32、路径
+//   三元组数 u32，然后按三元组排序的表，每一项：三元组 u32、它的倒排列表在倒排区中的结束位置 u64
+//   倒排区：每个三元组的文件编号列表，按从小到大排列，存相邻编号的差，用变长整数编码
+const MAGIC: &[u8; 8] = b"mgindex2";
+const TABLE_ENTRY: u64 = 12;
+
+// 修改时间（秒, 纳秒）和文件大小，任何一个变化都需要重新建立索引
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    modified: (u64, u32),
+    len: u64,
+}
+
+impl Stamp {
+    fn of(metadata: &fs::Metadata) -> Stamp {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or((0, 0), |since| (since.as_secs(), since.subsec_nanos()));
+        Stamp { modified, len: metadata.len() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    stamp: Stamp,
+    // 排好序的三元组，每个三元组用 24 位整数表示
+    trigrams: Vec<u32>,
+}
+
+// 建立索引时使用的完整内容，路径都是相对于被索引目录的
+#[derive(Debug, Default)]
+pub struct Index {
+    entries: BTreeMap<String, Entry>,
+}
+
+// 统一转成 ASCII 小写，这样同一份索引既能用于区分大小写的搜索，也能用于忽略大小写的搜索
+pub fn trigrams(bytes: &[u8]) -> Vec<u32> {
+    let mut result: Vec<u32> = bytes
+        .windows(3)
+        .map(|w| {
+            let w = [w[0].to_ascii_lowercase(), w[1].to_ascii_lowercase(), w[2].to_ascii_lowercase()];
+            (w[0] as u32) << 16 | (w[1] as u32) << 8 | w[2] as u32
+        })
+        .collect();
+    result.sort_unstable();
+    result.dedup();
+    result
+}
+
+// 匹配的行里一定包含的三元组，返回 None 表示索引帮不上忙，所有文件都需要检查
+pub fn query_trigrams(config: &Config, ignore_case: bool) -> Option<Vec<u32>> {
+    // 模糊匹配允许字符不同，压缩文件、归档和预处理器输出的内容和磁盘上的字节也不一样，
+    // 布尔表达式中的词不一定都要出现
+    if config.fuzzy.is_some()
+        || config.search_compressed
+        || config.search_archives
+        || config.expr
+        || config.pre.is_some()
+    {
+        return None;
+    }
+
+    let query = if config.multiline {
+        unescape(&config.query)
+    } else {
+        config.query.clone()
+    };
+    let bytes = query.as_bytes();
+
+    // 忽略大小写时，非 ASCII 字符的大小写形式可能是完全不同的字节，只能使用纯 ASCII 的三元组。
+    // 另外开尔文符号 K 和带点的 İ 转成小写后分别包含 k 和 i，所以含有这两个字母的三元组也不能用
+    let usable: Vec<u32> = trigrams(bytes)
+        .into_iter()
+        .filter(|&t| {
+            !ignore_case
+                || [t >> 16, (t >> 8) & 0xff, t & 0xff]
+                    .iter()
+                    .all(|&b| b < 0x80 && b != b'k' as u32 && b != b'i' as u32)
+        })
+        .collect();
+
+    if usable.is_empty() {
+        None
+    } else {
+        Some(usable)
+    }
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "corrupt minigrep index")
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+// 把文件编号列表编码成相邻编号的差，每 7 位一个字节，最高位表示后面还有字节
+fn encode_postings(ids: &[u32], out: &mut Vec<u8>) {
+    let mut previous = 0;
+    for &id in ids {
+        let mut delta = id - previous;
+        previous = id;
+        while delta >= 0x80 {
+            out.push(delta as u8 | 0x80);
+            delta >>= 7;
+        }
+        out.push(delta as u8);
+    }
+}
+
+fn decode_postings(bytes: &[u8], file_count: usize) -> io::Result<Vec<u32>> {
+    let mut ids = Vec::new();
+    let mut previous: u32 = 0;
+    let mut delta: u32 = 0;
+    let mut shift = 0;
+    for &byte in bytes {
+        if shift > 28 {
+            return Err(corrupt());
+        }
+        delta |= ((byte & 0x7f) as u32) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            previous = previous.checked_add(delta).ok_or_else(corrupt)?;
+            if previous as usize >= file_count || (!ids.is_empty() && delta == 0) {
+                return Err(corrupt());
+            }
+            ids.push(previous);
+            delta = 0;
+            shift = 0;
+        }
+    }
+    if shift != 0 {
+        return Err(corrupt());
+    }
+    Ok(ids)
+}
+
+// 打开的索引文件。文件列表在打开时读入，三元组表和倒排列表在查询时只读取需要的部分
+struct IndexFile {
+    file: File,
+    files: Vec<(String, Stamp)>,
+    // 三元组表和倒排区在索引文件中的位置
+    table: u64,
+    trigram_count: u64,
+    postings: u64,
+    len: u64,
+}
+
+impl IndexFile {
+    fn open(dir: &Path) -> io::Result<Option<IndexFile>> {
+        let file = match File::open(dir.join(INDEX_FILE)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        // 旧版本的索引或者别的文件都当作损坏，重新运行 index 子命令就会覆盖
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic).map_err(|_| corrupt())?;
+        if &magic != MAGIC {
+            return Err(corrupt());
+        }
+
+        let file_count = read_u32(&mut reader)?;
+        let mut files = Vec::new();
+        for _ in 0..file_count {
+            let modified = (read_u64(&mut reader)?, read_u32(&mut reader)?);
+            let stamp = Stamp { modified, len: read_u64(&mut reader)? };
+            let path_len = read_u32(&mut reader)? as u64;
+            if path_len > len {
+                return Err(corrupt());
+            }
+            let mut path = Vec::new();
+            (&mut reader).take(path_len).read_to_end(&mut path)?;
+            let path = String::from_utf8(path).map_err(|_| corrupt())?;
+            files.push((path, stamp));
+        }
+
+        let trigram_count = read_u32(&mut reader)? as u64;
+        let table = reader.stream_position()?;
+        let postings = table + trigram_count * TABLE_ENTRY;
+        if postings > len {
+            return Err(corrupt());
+        }
+
+        Ok(Some(IndexFile {
+            file: reader.into_inner(),
+            files,
+            table,
+            trigram_count,
+            postings,
+            len,
+        }))
+    }
+
+    // 三元组表的第 i 项：三元组和它的倒排列表的结束位置
+    fn table_entry(&mut self, i: u64) -> io::Result<(u32, u64)> {
+        self.file.seek(SeekFrom::Start(self.table + i * TABLE_ENTRY))?;
+        Ok((read_u32(&mut self.file)?, read_u64(&mut self.file)?))
+    }
+
+    // 读取表中第 i 项的倒排列表
+    fn postings_at(&mut self, i: u64) -> io::Result<Vec<u32>> {
+        let start = if i == 0 { 0 } else { self.table_entry(i - 1)?.1 };
+        let end = self.table_entry(i)?.1;
+        if start > end || self.postings + end > self.len {
+            return Err(corrupt());
+        }
+
+        let mut bytes = vec![0; (end - start) as usize];
+        self.file.seek(SeekFrom::Start(self.postings + start))?;
+        self.file.read_exact(&mut bytes)?;
+        decode_postings(&bytes, self.files.len())
+    }
+
+    // 包含这个三元组的文件编号，在三元组表中二分查找，不需要读入整个表
+    fn postings(&mut self, trigram: u32) -> io::Result<Vec<u32>> {
+        let (mut low, mut high) = (0, self.trigram_count);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let (found, _) = self.table_entry(middle)?;
+            if found < trigram {
+                low = middle + 1;
+            } else if found > trigram {
+                high = middle;
+            } else {
+                return self.postings_at(middle);
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    // 读入全部内容并转换回每个文件的三元组，增量更新索引时需要
+    fn into_index(mut self) -> io::Result<Index> {
+        let mut table = vec![0; (self.postings - self.table) as usize];
+        self.file.seek(SeekFrom::Start(self.table))?;
+        self.file.read_exact(&mut table)?;
+        let mut postings = Vec::new();
+        self.file.read_to_end(&mut postings)?;
+
+        let mut trigrams = vec![Vec::new(); self.files.len()];
+        let mut start = 0;
+        for item in table.chunks_exact(TABLE_ENTRY as usize) {
+            let trigram = u32::from_le_bytes(item[..4].try_into().unwrap());
+            let end = u64::from_le_bytes(item[4..].try_into().unwrap()) as usize;
+            let bytes = postings.get(start..end).ok_or_else(corrupt)?;
+            for id in decode_postings(bytes, self.files.len())? {
+                trigrams[id as usize].push(trigram);
+            }
+            start = end;
+        }
+
+        let entries = self
+            .files
+            .into_iter()
+            .zip(trigrams)
+            .map(|((path, stamp), trigrams)| (path, Entry { stamp, trigrams }))
+            .collect();
+        Ok(Index { entries })
+    }
+}
+
+impl Index {
+    pub fn load(dir: &Path) -> io::Result<Option<Index>> {
+        IndexFile::open(dir)?.map(IndexFile::into_index).transpose()
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        // 倒排：每个三元组出现在哪些文件中，文件编号就是它在 entries 中的顺序
+        let mut inverted: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+        for (id, entry) in self.entries.values().enumerate() {
+            for &trigram in &entry.trigrams {
+                inverted.entry(trigram).or_default().push(id as u32);
+            }
+        }
+
+        // 先写到临时文件再改名，避免搜索时读到写了一半的索引
+        let tmp = dir.join(format!("{}.tmp", INDEX_FILE));
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        out.write_all(MAGIC)?;
+
+        out.write_all(&(self.entries.len() as u32).to_le_bytes())?;
+        for (path, entry) in &self.entries {
+            out.write_all(&entry.stamp.modified.0.to_le_bytes())?;
+            out.write_all(&entry.stamp.modified.1.to_le_bytes())?;
+            out.write_all(&entry.stamp.len.to_le_bytes())?;
+            out.write_all(&(path.len() as u32).to_le_bytes())?;
+            out.write_all(path.as_bytes())?;
+        }
+
+        let mut postings = Vec::new();
+        out.write_all(&(inverted.len() as u32).to_le_bytes())?;
+        for (trigram, ids) in &inverted {
+            encode_postings(ids, &mut postings);
+            out.write_all(&trigram.to_le_bytes())?;
+            out.write_all(&(postings.len() as u64).to_le_bytes())?;
+        }
+        out.write_all(&postings)?;
+
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(tmp, dir.join(INDEX_FILE))
+    }
+}
+
+// 相对于被索引目录的路径，不是 UTF-8 的路径不放进索引，搜索时总是会直接检查
+fn relative_path(dir: &Path, path: &Path) -> Option<String> {
+    Some(path.strip_prefix(dir).ok()?.to_str()?.to_string())
+}
+
+// 建立或者增量更新目录的索引，只有修改时间或大小变化了的文件才会重新读取，
+// 返回 (索引中的文件数, 重新读取的文件数)
+pub fn build(dir: &Path) -> Result<(usize, usize), MinigrepError> {
+    if !fs::metadata(dir).map_err(MinigrepError::io(dir))?.is_dir() {
+        return Err(MinigrepError::Argument(format!("cannot index {}: not a directory", dir.display())));
+    }
+    let old = Index::load(dir).unwrap_or(None).unwrap_or_default();
+    let index_path = dir.join(INDEX_FILE);
+    let mut index = Index::default();
+    let mut updated = 0;
+
+    for path in collect_files(&[dir.to_path_buf()])? {
+        let relative = match relative_path(dir, &path) {
+            Some(relative) => relative,
+            None => continue,
+        };
+        let stamp = Stamp::of(&fs::metadata(&path).map_err(MinigrepError::io(&path))?);
+
+        let entry = match old.entries.get(&relative).filter(|entry| entry.stamp == stamp) {
+            Some(entry) => entry.clone(),
+            None => {
+                updated += 1;
+                Entry {
+                    stamp,
+                    trigrams: trigrams(&fs::read(&path).map_err(MinigrepError::io(&path))?),
+                }
+            }
+        };
+        index.entries.insert(relative, entry);
+    }
+
+    index.save(dir).map_err(MinigrepError::io(&index_path))?;
+    Ok((index.entries.len(), updated))
+}
+
+// 在索引中查出包含所有三元组的文件，返回每个已索引文件的时间戳和它是否可能匹配
+fn indexed_matches(mut index: IndexFile, needed: &[u32]) -> io::Result<HashMap<String, (Stamp, bool)>> {
+    let mut ids: Option<Vec<u32>> = None;
+    for &trigram in needed {
+        let found = index.postings(trigram)?;
+        let ids = ids.get_or_insert_with(|| found.clone());
+        ids.retain(|id| found.binary_search(id).is_ok());
+        if ids.is_empty() {
+            break;
+        }
+    }
+
+    let ids = ids.unwrap_or_default();
+    Ok(index
+        .files
+        .into_iter()
+        .enumerate()
+        .map(|(id, (path, stamp))| (path, (stamp, ids.binary_search(&(id as u32)).is_ok())))
+        .collect())
+}
+
+// 用索引缩小需要搜索的文件范围。没有索引的目录、索引之后新增或修改过的文件都会保留下来，
+// 所以结果和不使用索引时完全一样
+pub fn candidate_files(config: &Config) -> Result<Vec<PathBuf>, MinigrepError> {
+    let ignore_case = config.case_mode.ignore_case(&config.query);
+    let needed = query_trigrams(config, ignore_case);
+    let mut files = Vec::new();
+
+    for dir in &config.file_paths {
+        let walked = collect_files(std::slice::from_ref(dir))?;
+        let indexed = match &needed {
+            Some(needed) if dir.is_dir() => {
+                let index_path = dir.join(INDEX_FILE);
+                IndexFile::open(dir)
+                    .and_then(|index| index.map(|index| indexed_matches(index, needed)).transpose())
+                    .map_err(MinigrepError::io(&index_path))?
+            }
+            _ => None,
+        };
+
+        for path in walked {
+            let keep = match &indexed {
+                Some(indexed) => {
+                    let entry = relative_path(dir, &path).and_then(|relative| indexed.get(&relative));
+                    match entry {
+                        Some(&(stamp, matches)) => {
+                            matches || fs::metadata(&path).map_or(true, |metadata| Stamp::of(&metadata) != stamp)
+                        }
+                        None => true,
+                    }
+                }
+                None => true,
+            };
+            if keep {
+                files.push(path);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Temp;
+
+    fn config(args: &[&str]) -> Config {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Config::build_from(&args, &[], None).unwrap()
+    }
+
+    #[test]
+    fn index_narrows_candidates() {
+        let dir = Temp::dir("index");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("poem.txt"), include_str!("../../poem.txt")).unwrap();
+        fs::write(dir.join("sub/rust.txt"), "Rust:\nsafe, fast, productive.\n").unwrap();
+
+        assert_eq!((2, 2), build(&dir).unwrap());
+        // 没有变化的文件不会重新读取
+        assert_eq!((2, 0), build(&dir).unwrap());
+
+        let root = dir.to_string_lossy().into_owned();
+        let only = |query: &str, extra: &[&str]| {
+            let mut args = vec!["minigrep", "--index"];
+            args.extend_from_slice(extra);
+            args.extend_from_slice(&[query, &root]);
+            let files = candidate_files(&config(&args)).unwrap();
+            files.iter().map(|path| relative_path(&dir, path).unwrap()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec!["poem.txt"], only("nobody", &[]));
+        assert_eq!(vec!["sub/rust.txt"], only("PRODUCTIVE", &["-i"]));
+        // 查询太短时无法使用索引
+        assert_eq!(vec!["poem.txt", "sub/rust.txt"], only("to", &[]));
+
+        // 修改过的文件即使索引中没有对应的三元组也要检查
+        fs::write(dir.join("sub/rust.txt"), "Rust:\nnobody\n").unwrap();
+        assert_eq!(vec!["poem.txt", "sub/rust.txt"], only("nobody", &[]));
+        assert_eq!((2, 1), build(&dir).unwrap());
+    }
+
+    #[test]
+    fn index_is_much_smaller_than_the_files() {
+        let dir = Temp::dir("index-size");
+        // 用诗里的词随机组成文件，和真实的目录一样，不同文件共用大部分三元组
+        let words: Vec<&str> = include_str!("../../poem.txt").split_whitespace().collect();
+        let mut seed: u32 = 1;
+        let mut corpus = 0;
+        for file in 0..50 {
+            let mut text = String::new();
+            while text.len() < 20_000 {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                text.push_str(words[(seed >> 16) as usize % words.len()]);
+                text.push(if seed.is_multiple_of(7) { '\n' } else { ' ' });
+            }
+            corpus += text.len();
+            fs::write(dir.join(format!("{}.txt", file)), text).unwrap();
+        }
+
+        assert_eq!((50, 50), build(&dir).unwrap());
+        let size = fs::metadata(dir.join(INDEX_FILE)).unwrap().len() as usize;
+        assert!(size * 10 < corpus, "index is {} bytes for {} bytes of files", size, corpus);
+
+        // 重新读入的索引和建立时的内容相同
+        let index = Index::load(&dir).unwrap().unwrap();
+        let first = index.entries.get("0.txt").unwrap();
+        assert_eq!(trigrams(&fs::read(dir.join("0.txt")).unwrap()), first.trigrams);
+    }
+}
@@ -0,0 +1,248 @@
+// 简单的词法分析：把 Rust 和 C 风格语言的源码分成代码、注释和字符串，
+// --code-only 和 --comments-only 只在对应的部分中匹配
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    // 不包括注释和字符串、字符字面量
+    Code,
+    Comments,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Code,
+    Comment,
+    Literal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    // 块注释可以嵌套，有原始字符串，' 也可能是生命周期
+    Rust,
+    CLike,
+    // 和 C 一样，但是反引号中是原始字符串，反斜杠没有特殊含义
+    Go,
+}
+
+impl Scope {
+    fn contains(self, kind: Kind) -> bool {
+        matches!((self, kind), (Scope::Code, Kind::Code) | (Scope::Comments, Kind::Comment))
+    }
+}
+
+// 按扩展名判断语言，归档中的文件（archive.zip!/src/main.rs）也一样
+fn language(path: &Path) -> Option<Language> {
+    let extension = path.extension()?.to_str()?;
+    match extension {
+        "rs" => Some(Language::Rust),
+        "go" => Some(Language::Go),
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "java" | "js" | "ts" | "cs" | "kt" | "swift" | "scala" => {
+            Some(Language::CLike)
+        }
+        _ => None,
+    }
+}
+
+// 能不能区分这个文件中的代码和注释，不能的文件在 --code-only 和 --comments-only 时跳过
+pub fn supports(path: &Path) -> bool {
+    language(path).is_some()
+}
+
+// 把不在 scope 中的字节换成 NUL，换行符保留，这样行的划分和每个字节的位置都不变，查询也不会匹配到被去掉的部分。
+// 不认识的语言返回 None
+pub fn mask(bytes: &[u8], path: &Path, scope: Scope) -> Option<Vec<u8>> {
+    let kinds = classify(bytes, language(path)?);
+    Some(
+        bytes
+            .iter()
+            .zip(kinds)
+            .map(|(&b, kind)| if b == b'\n' || scope.contains(kind) { b } else { 0 })
+            .collect(),
+    )
+}
+
+fn is_ident(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+// 每个字节属于哪一类
+fn classify(bytes: &[u8], language: Language) -> Vec<Kind> {
+    let mut kinds = vec![Kind::Code; bytes.len()];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        let end = if rest.starts_with(b"//") {
+            Some((i + rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len()), Kind::Comment))
+        } else if rest.starts_with(b"/*") {
+            Some((block_comment_end(bytes, i, language == Language::Rust), Kind::Comment))
+        } else if rest[0] == b'"' || (language != Language::Rust && rest[0] == b'`') {
+            let escapes = !(language == Language::Go && rest[0] == b'`');
+            Some((quoted_end(bytes, i, rest[0], escapes), Kind::Literal))
+        } else if rest[0] == b'\'' {
+            char_literal_end(bytes, i, language).map(|end| (end, Kind::Literal))
+        } else if language == Language::Rust && (i == 0 || !is_ident(bytes[i - 1])) {
+            prefixed_literal_end(bytes, i).map(|end| (end, Kind::Literal))
+        } else {
+            None
+        };
+
+        match end {
+            Some((end, kind)) => {
+                kinds[i..end].fill(kind);
+                i = end;
+            }
+            // 标识符整个跳过，避免把 bar"x" 中间的 r 当作原始字符串的前缀
+            None if is_ident(rest[0]) => i += rest.iter().position(|&b| !is_ident(b)).unwrap_or(rest.len()),
+            None => i += 1,
+        }
+    }
+    kinds
+}
+
+// 块注释结束后的位置，没有结束时到文件末尾
+fn block_comment_end(bytes: &[u8], start: usize, nested: bool) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"/*") && (nested || depth == 0) {
+            depth += 1;
+            i += 2;
+        } else if bytes[i..].starts_with(b"*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    bytes.len()
+}
+
+// 从引号开始到对应的结束引号之后，escapes 为 false 时反斜杠没有特殊含义（原始字符串）
+fn quoted_end(bytes: &[u8], start: usize, quote: u8, escapes: bool) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if escapes => i += 2,
+            b if b == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+// 字符字面量的结束位置。Rust 中 'a 可能是生命周期或者标签，这时返回 None；
+// C 风格语言中没有结束引号时只到行尾
+fn char_literal_end(bytes: &[u8], start: usize, language: Language) -> Option<usize> {
+    let rest = &bytes[start + 1..];
+    match language {
+        Language::Rust => {
+            // 转义的字符一定是字面量，否则只有 'x' 这样正好一个字符的才是
+            if rest.first() == Some(&b'\\') {
+                return Some(quoted_end(bytes, start, b'\'', true));
+            }
+            let len = utf8_len(*rest.first()?);
+            (rest.get(len) == Some(&b'\'')).then_some(start + len + 2)
+        }
+        Language::CLike | Language::Go => {
+            let line = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            Some(quoted_end(&bytes[..start + 1 + line], start, b'\'', true))
+        }
+    }
+}
+
+fn utf8_len(first: u8) -> usize {
+    match first {
+        0xf0.. => 4,
+        0xe0.. => 3,
+        0xc0.. => 2,
+        _ => 1,
+    }
+}
+
+// Rust 中带前缀的字面量：b"..."、b'x'、c"..."、r"..."、r#"..."#、br##"..."## 等
+fn prefixed_literal_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start;
+    if matches!(bytes.get(i), Some(b'b' | b'c')) {
+        i += 1;
+        match bytes.get(i) {
+            Some(b'"') => return Some(quoted_end(bytes, i, b'"', true)),
+            Some(b'\'') if bytes[start] == b'b' => return Some(quoted_end(bytes, i, b'\'', true)),
+            _ => {}
+        }
+    }
+    if bytes.get(i) != Some(&b'r') {
+        return None;
+    }
+    i += 1;
+
+    // 原始字符串：r 后面是若干个 #，然后是 "，以 " 加上同样多的 # 结束
+    let hashes = bytes[i..].iter().take_while(|&&b| b == b'#').count();
+    i += hashes;
+    if bytes.get(i) != Some(&b'"') {
+        return None;
+    }
+    let mut closing = vec![b'"'];
+    closing.resize(hashes + 1, b'#');
+    let end = bytes[i + 1..]
+        .windows(closing.len())
+        .position(|window| window == closing.as_slice())
+        .map_or(bytes.len(), |offset| i + 1 + offset + closing.len());
+    Some(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn only(source: &str, path: &str, scope: Scope) -> String {
+        String::from_utf8(mask(source.as_bytes(), Path::new(path), scope).unwrap())
+            .unwrap()
+            .split('\n')
+            .map(|line| line.split('\0').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("|"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn rust_spans() {
+        let source = "\
+let s = \"// not a comment\"; // 注释 \"quoted\"
+/* outer /* inner */ still */ f::<'a>(b'x', '中', '\\'', r#\"raw \"quote\" end\"#);
+let raw_identifier = br\"x\\\"; bar(\"y\")";
+
+        assert_eq!(
+            "let s = |; \n f::<'a>(|, |, |, |);\nlet raw_identifier = |; bar(|)",
+            only(source, "src/main.rs", Scope::Code)
+        );
+        assert_eq!("// 注释 \"quoted\"\n/* outer /* inner */ still */\n", only(source, "src/main.rs", Scope::Comments));
+    }
+
+    #[test]
+    fn c_like_and_unknown_languages() {
+        let source = "/* a /* b */ int c = 'x'; char *d = \"e\\\"\"; // f\ndon't";
+        assert_eq!(
+            " int c = |; char *d = |; \ndon",
+            only(source, "main.c", Scope::Code)
+        );
+        // 没有结束的字符字面量只到行尾
+        assert_eq!("/* a /* b */|// f\n", only(source, "main.c", Scope::Comments));
+        assert!(mask(source.as_bytes(), Path::new("notes.txt"), Scope::Code).is_none());
+        assert!(!supports(Path::new("notes.txt")) && supports(Path::new("main.go")));
+    }
+
+    #[test]
+    fn go_raw_strings() {
+        // 反引号中的反斜杠不转义，C:\dir\ 之后的反引号就是结束
+        let source = "path := `C:\\dir\\` // 目录\nmsg := \"a\\\"b\" + `c`";
+        assert_eq!("path := | \nmsg := | + ", only(source, "main.go", Scope::Code));
+        assert_eq!("// 目录\n", only(source, "main.go", Scope::Comments));
+        // JavaScript 的模板字符串中反斜杠仍然转义
+        assert_eq!("x = ", only("x = `a\\` // b`", "main.js", Scope::Code));
+        assert_eq!("x = | ", only("x = `a\\` // b`", "main.go", Scope::Code));
+    }
+}
@@ -1,69 +1,1030 @@
-use std::fs;
-use std::error::Error; // 任何实现了 Error trait 的类型都可以使用 dyn Error 作为返回值
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+mod error;
+pub use error::{display_chain, MinigrepError};
 
 mod search;
-use search::search;
+pub use search::{
+    byte_lines, search, search_bytes, search_bytes_numbered, search_numbered, LineMatch, LineMatcher, Matcher,
+};
 
 mod case_insensitive;
-use case_insensitive::search_case_insensitive;
+pub use case_insensitive::search_case_insensitive;
+pub use case_insensitive::CaseMode;
 
 use std::env;
 
+mod config_file;
+
+mod decompress;
+
+mod archive;
+
+mod fuzzy;
+pub use fuzzy::{FuzzyMatch, FuzzyMatcher};
+
+mod tui;
+
+mod watch;
+
+mod follow;
+
+mod index;
+
+mod stats;
+use stats::{FileStats, Stats};
+
+mod multiline;
+use multiline::{search_multiline, unescape};
+
+mod options;
+pub use options::{Generate, Shell};
+
+mod field;
+
+mod expr;
+pub use expr::{parse as parse_expr, Expr};
+
+mod preprocess;
+
+mod timerange;
+pub use timerange::TimeRange;
+
+mod lexer;
+pub use lexer::Scope;
+
+mod sort;
+pub use sort::SortKey;
+use sort::Unique;
+
+#[cfg(test)]
+mod testing;
+
+#[derive(Clone)]
 pub struct Config {
     pub query: String,
-    pub file_path: String,
-    pub ignore_case: bool,
+    pub file_paths: Vec<PathBuf>,
+    pub case_mode: CaseMode,
+    // 是否自动解压 gzip 等压缩文件后再搜索
+    pub search_compressed: bool,
+    // 把 tar 和 zip 归档当作目录，搜索其中的每个文件
+    pub search_archives: bool,
+    // 多行模式下查询可以跨越多行，查询中的 \n 会被当作换行符
+    pub multiline: bool,
+    pub line_number: bool,
+    // 在行号后面输出最佳匹配开始的列（从 1 开始的字节位置）
+    pub column: bool,
+    // 每个文件最多输出多少行匹配结果
+    pub max_count: Option<usize>,
+    // -q 不输出任何内容，只通过退出码表示是否有匹配
+    pub quiet: bool,
+    // -l 只输出有匹配的文件名
+    pub files_with_matches: bool,
+    // 模糊匹配允许的最大编辑距离
+    pub fuzzy: Option<usize>,
+    // 交互模式，在终端中浏览结果并实时修改查询
+    pub interactive: bool,
+    // 是否在每行结果前面加上文件名，搜索多个文件或目录时默认打开
+    pub with_filename: bool,
+    // 文件变化时自动重新搜索
+    pub watch: bool,
+    // 搜索完已有内容后继续读取文件中追加的内容
+    pub follow: bool,
+    // `minigrep index DIR...` 建立三元组索引，而不是搜索
+    pub build_index: bool,
+    // 搜索目录时使用已有的索引缩小需要检查的文件范围
+    pub use_index: bool,
+    // 普通搜索结束后在标准错误中输出统计信息
+    pub stats: bool,
+    // 按什么顺序搜索文件，None 表示按命令行参数和目录中文件名的顺序
+    pub sort: Option<SortKey>,
+    // --sortr 按相反的顺序
+    pub sort_reverse: bool,
+    // 相同的行只输出一次，不加文件名和行号
+    pub unique: bool,
+    // --unique-count 在每行前面加上出现的次数
+    pub unique_count: bool,
+    // -0 文件名后面用 NUL 而不是换行符或冒号结尾，文件名中有空格或换行符时也能安全地交给 xargs -0
+    pub null: bool,
+    // 输出路径时用这个字符代替系统的路径分隔符
+    pub path_separator: Option<char>,
+    // 某些文件读取失败时不在标准错误中输出提示，退出码仍然会反映出错
+    pub no_messages: bool,
+    // 输出补全脚本或 man 手册，而不是搜索
+    pub generate: Option<Generate>,
+    // 只在每一行的第几列中匹配（从 1 开始），输出时仍然是整行
+    pub field: Option<usize>,
+    // --field 使用的分隔符，默认是逗号
+    pub delimiter: u8,
+    // 查询是 --expr 给出的布尔表达式，而不是第一个位置参数
+    pub expr: bool,
+    // 搜索之前用这个命令转换文件，搜索它的标准输出
+    pub pre: Option<String>,
+    // 只有匹配这些模式的文件才交给 --pre 的命令，为空时所有文件都要转换
+    pub pre_globs: Vec<String>,
+    // 只搜索行首时间戳在这个范围内的行
+    pub time_range: Option<TimeRange>,
+    // --code-only 或 --comments-only，只在源码的这一部分中匹配
+    pub scope: Option<Scope>,
+}
+
+// --since、--until 和 --time-format 的原始值，配置文件和命令行中的都解析完之后才能组合成 TimeRange
+#[derive(Default)]
+struct TimeArgs {
+    since: Option<String>,
+    until: Option<String>,
+    format: Option<String>,
 }
 
 impl Config {
     // new一般不会报错，所以改名为build
-    pub fn build(args: &[String]) -> Result<Config, &'static str> {
-        // 如果传入的参数不够，就给出提示
-        if args.len() < 3 {
-            return Err("not enough arguments");
-        }
-
-        let query = args[1].clone();
-        let file_path = args[2].clone();
-
-        // 首先检查环境变量中是否有IGNORE_CASE
-        let ignore_case = match env::var("IGNORE_CASE") {
-            // 如果有，就使用环境变量中的值
-            Ok(flag) => match flag.as_str() {
-                "0" => false,
-                _ => true,
-            },
-            // 如果没有，就检查命令行参数中是否有ig, igc, ignore, ignore_case
-            Err(_) => {
-                match args.get(3) {
-                    Some(arg) => match arg.as_str() {
-                        "ig" | "igc" | "ignore" | "ignore_case" | "IGNORE_CASE" => true,
-                        _ => false,
-                    },
-                    None => false,
+    // 参数的优先级：配置文件 < 环境变量 < 命令行参数
+    // 参数来自 env::args_os()，文件路径可以不是 UTF-8
+    pub fn build<S: AsRef<OsStr>>(args: &[S]) -> Result<Config, MinigrepError> {
+        // --no-config 可以出现在命令行的任何位置，但 -- 之后的参数是查询和文件名，不算选项
+        let no_config = args
+            .iter()
+            .skip(1)
+            .map(AsRef::as_ref)
+            .take_while(|arg| *arg != "--")
+            .any(|arg| arg == "--no-config");
+        let defaults = if no_config {
+            Vec::new()
+        } else {
+            config_file::load_default_args()?
+        };
+
+        let ignore_case_env = config_file::bool_env("IGNORE_CASE")?;
+
+        Config::build_from(args, &defaults, ignore_case_env)
+    }
+
+    fn build_from<S: AsRef<OsStr>>(
+        args: &[S],
+        defaults: &[String],
+        ignore_case_env: Option<bool>,
+    ) -> Result<Config, MinigrepError> {
+        let mut config = Config {
+            query: String::new(),
+            file_paths: Vec::new(),
+            case_mode: CaseMode::Sensitive,
+            search_compressed: false,
+            search_archives: false,
+            multiline: false,
+            line_number: false,
+            column: false,
+            max_count: None,
+            quiet: false,
+            files_with_matches: false,
+            fuzzy: None,
+            interactive: false,
+            with_filename: false,
+            watch: false,
+            follow: false,
+            build_index: false,
+            use_index: false,
+            stats: false,
+            sort: None,
+            sort_reverse: false,
+            unique: false,
+            unique_count: false,
+            null: false,
+            path_separator: None,
+            no_messages: false,
+            generate: None,
+            field: None,
+            delimiter: b',',
+            expr: false,
+            pre: None,
+            pre_globs: Vec::new(),
+            time_range: None,
+            scope: None,
+        };
+
+        let mut time = TimeArgs::default();
+        // 配置文件中只能写选项，不能写查询内容和文件路径
+        if !config.apply_args(defaults, &mut time)?.is_empty() {
+            return Err(MinigrepError::Argument("config file may only contain flags".to_string()));
+        }
+
+        if let Some(ignore_case) = ignore_case_env {
+            config.case_mode = if ignore_case {
+                CaseMode::Insensitive
+            } else {
+                CaseMode::Sensitive
+            };
+        }
+
+        // index 子命令只需要目录参数，要搜索 "index" 这个词可以写成 `minigrep -- index FILE`
+        if is_index_command(args.get(1..).unwrap_or_default()) {
+            config.build_index = true;
+            let mut dirs = config.apply_args(&args[1..], &mut time)?;
+            dirs.remove(0);
+            if dirs.is_empty() {
+                dirs.push(OsString::from("."));
+            }
+            config.file_paths = dirs.into_iter().map(PathBuf::from).collect();
+            return Ok(config);
+        }
+
+        let mut positional = config.apply_args(args.get(1..).unwrap_or_default(), &mut time)?;
+
+        // 生成补全脚本和 man 手册时不需要查询和文件
+        if config.generate.is_some() {
+            return Ok(config);
+        }
+
+        // 如果传入的参数不够，就给出提示。--expr 已经给出了查询，只需要文件
+        let needed = if config.expr { 1 } else { 2 };
+        if positional.len() < needed {
+            return Err(MinigrepError::Argument("not enough arguments".to_string()));
+        }
+
+        // 兼容旧的用法：只有三个参数且第三个参数是 ig, igc, ignore, ignore_case 时忽略大小写
+        if !config.expr
+            && positional.len() == 3
+            && matches!(
+                positional[2].to_str(),
+                Some("ig" | "igc" | "ignore" | "ignore_case" | "IGNORE_CASE")
+            )
+        {
+            config.case_mode = CaseMode::Insensitive;
+            positional.pop();
+        }
+
+        if !config.expr {
+            config.query = utf8_arg(&positional.remove(0))?.to_string();
+        }
+        config.file_paths = positional.into_iter().map(PathBuf::from).collect();
+
+        if config.follow
+            && (config.watch
+                || config.multiline
+                || config.search_compressed
+                || config.files_with_matches
+                || config.pre.is_some())
+        {
+            return Err(MinigrepError::Argument(
+                "--follow cannot be combined with --watch, --multiline, --search-zip, --files-with-matches or --pre"
+                    .to_string(),
+            ));
+        }
+
+        if !config.pre_globs.is_empty() && config.pre.is_none() {
+            return Err(MinigrepError::Argument("--pre-glob requires --pre".to_string()));
+        }
+
+        // 这几种情况下一行没有单一的匹配位置
+        if config.column && (config.multiline || config.expr || config.field.is_some()) {
+            return Err(MinigrepError::Argument(
+                "--column cannot be combined with --multiline, --expr or --field".to_string(),
+            ));
+        }
+
+        if config.field.is_some() && config.multiline {
+            return Err(MinigrepError::Argument("--field cannot be combined with --multiline".to_string()));
+        }
+
+        if config.expr {
+            if config.multiline || config.interactive {
+                return Err(MinigrepError::Argument(
+                    "--expr cannot be combined with --multiline or --interactive".to_string(),
+                ));
+            }
+            // 提前解析表达式，语法错误时直接报告出错的位置
+            line_matcher(&config, &config.query, false)?;
+        }
+
+        if config.scope.is_some() && (config.multiline || config.follow || config.interactive) {
+            return Err(MinigrepError::Argument(
+                "--code-only and --comments-only cannot be combined with --multiline, --follow or --interactive"
+                    .to_string(),
+            ));
+        }
+
+        if time.since.is_some() || time.until.is_some() {
+            if config.multiline || config.follow || config.interactive {
+                return Err(MinigrepError::Argument(
+                    "--since and --until cannot be combined with --multiline, --follow or --interactive".to_string(),
+                ));
+            }
+            config.time_range = Some(TimeRange::new(
+                time.format.as_deref(),
+                time.since.as_deref(),
+                time.until.as_deref(),
+            )?);
+        }
+
+        if config.fuzzy.is_some() {
+            if config.multiline {
+                return Err(MinigrepError::Argument("--fuzzy cannot be combined with --multiline".to_string()));
+            }
+            // --expr 的长度限制针对每个词，创建匹配器时检查
+            if !config.expr && config.query.chars().count() > fuzzy::MAX_QUERY_LEN {
+                return Err(MinigrepError::Pattern(format!(
+                    "fuzzy queries can be at most {} characters long",
+                    fuzzy::MAX_QUERY_LEN
+                )));
+            }
+        }
+
+        Ok(config)
+    }
+
+    // 是否在结果前面打印启动信息。-q 和 -l 的输出需要能被其它程序直接使用，
+    // 交互模式会占用整个终端，建立索引和生成补全脚本时也没有在搜索，所以都不打印
+    pub fn show_banner(&self) -> bool {
+        !self.quiet
+            && !self.files_with_matches
+            && !self.null
+            && !self.interactive
+            && !self.build_index
+            && self.generate.is_none()
+    }
+
+    // 输出中显示的路径，保留原来的字节，不是 UTF-8 的文件名也能原样交给其它程序
+    fn display_path<'p>(&self, path: &'p Path) -> Cow<'p, [u8]> {
+        let bytes = path_bytes(path);
+        let separator = match self.path_separator {
+            Some(separator) => separator,
+            None => return bytes,
+        };
+
+        let mut encoded = [0; 4];
+        let separator = separator.encode_utf8(&mut encoded).as_bytes();
+        let mut shown = Vec::with_capacity(bytes.len());
+        for &byte in bytes.iter() {
+            if byte == std::path::MAIN_SEPARATOR as u8 {
+                shown.extend_from_slice(separator);
+            } else {
+                shown.push(byte);
+            }
+        }
+        Cow::Owned(shown)
+    }
+
+    // 依次应用参数中的选项，返回剩下的位置参数
+    fn apply_args<S: AsRef<OsStr>>(
+        &mut self,
+        args: &[S],
+        time: &mut TimeArgs,
+    ) -> Result<Vec<OsString>, MinigrepError> {
+        let mut positional = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let arg = arg.as_ref();
+            // -- 之后的参数全部当作位置参数，方便搜索以 - 开头的内容
+            if arg == "--" {
+                positional.extend(args.by_ref().map(|arg| arg.as_ref().to_owned()));
+                continue;
+            }
+            // 单独的 - 也是位置参数
+            if !arg.as_encoded_bytes().starts_with(b"-") || arg == "-" {
+                positional.push(arg.to_owned());
+                continue;
+            }
+            let arg = utf8_arg(arg)?;
+
+            // 支持 --name=value 的写法，短选项的值也可以直接跟在后面，比如 -m5
+            let (option, inline) = options::lookup(arg)
+                .ok_or_else(|| MinigrepError::Argument(format!("unknown flag: {}", arg)))?;
+            let flag = format!("--{}", option.long);
+            let value = match (option.value, inline) {
+                (Some(_), _) => flag_value(&flag, inline, &mut args)?,
+                (None, None) => String::new(),
+                (None, Some(_)) => return Err(MinigrepError::Argument(format!("{} does not take a value", flag))),
+            };
+
+            match option.long {
+                "ignore-case" => self.case_mode = CaseMode::Insensitive,
+                "case-sensitive" => self.case_mode = CaseMode::Sensitive,
+                "smart-case" => self.case_mode = CaseMode::Smart,
+                "search-zip" => self.search_compressed = true,
+                "search-archives" => self.search_archives = true,
+                "multiline" => self.multiline = true,
+                "line-number" => self.line_number = true,
+                "column" => self.column = true,
+                "max-count" => {
+                    self.max_count = Some(value.parse().map_err(|_| number_error(&flag, &value))?);
+                }
+                "quiet" => self.quiet = true,
+                "files-with-matches" => self.files_with_matches = true,
+                "fuzzy" => {
+                    self.fuzzy = Some(value.parse().map_err(|_| number_error(&flag, &value))?);
+                }
+                "interactive" => self.interactive = true,
+                "watch" => self.watch = true,
+                "follow" => self.follow = true,
+                "index" => self.use_index = true,
+                "stats" => self.stats = true,
+                "sort" | "sortr" => {
+                    self.sort = Some(SortKey::parse(&value)?);
+                    self.sort_reverse = option.long == "sortr";
+                }
+                "unique" => self.unique = true,
+                "unique-count" => {
+                    self.unique = true;
+                    self.unique_count = true;
+                }
+                "null" => self.null = true,
+                "path-separator" => {
+                    let mut chars = value.chars();
+                    self.path_separator = match (chars.next(), chars.next()) {
+                        (Some(separator), None) => Some(separator),
+                        _ => {
+                            return Err(MinigrepError::Argument(
+                                "--path-separator expects a single character".to_string(),
+                            ))
+                        }
+                    };
+                }
+                "field" => {
+                    self.field = match value.parse() {
+                        Ok(0) | Err(_) => {
+                            return Err(MinigrepError::Argument(format!(
+                                "--field expects a field number starting from 1, got {}",
+                                value
+                            )))
+                        }
+                        Ok(n) => Some(n),
+                    };
+                }
+                "delimiter" => {
+                    self.delimiter = field::parse_delimiter(&value).ok_or_else(|| {
+                        MinigrepError::Argument(format!(
+                            "--delimiter expects a single ASCII character other than \", got {}",
+                            value
+                        ))
+                    })?;
+                }
+                "expr" => {
+                    self.query = value;
+                    self.expr = true;
+                }
+                "pre" => self.pre = Some(value),
+                "pre-glob" => self.pre_globs.push(value),
+                "since" => time.since = Some(value),
+                "until" => time.until = Some(value),
+                "time-format" => time.format = Some(value),
+                "code-only" => self.scope = Some(Scope::Code),
+                "comments-only" => self.scope = Some(Scope::Comments),
+                "no-messages" => self.no_messages = true,
+                "no-config" => {}
+                "generate" => {
+                    // --generate completions 后面还要跟一个 shell 的名字
+                    let shell = match value.as_str() {
+                        "completions" => args.next().map(|shell| utf8_arg(shell.as_ref())).transpose()?,
+                        _ => None,
+                    };
+                    self.generate = Some(Generate::parse(&value, shell)?);
                 }
+                long => unreachable!("--{} is in the option table but not handled", long),
             }
+        }
+
+        Ok(positional)
+    }
+}
+
+// 选项的值可以写在等号后面，也可以是下一个参数
+fn flag_value<S: AsRef<OsStr>>(
+    flag: &str,
+    inline: Option<&str>,
+    args: &mut std::slice::Iter<S>,
+) -> Result<String, MinigrepError> {
+    match inline {
+        Some(value) => Ok(value.to_string()),
+        None => {
+            let value = args
+                .next()
+                .ok_or_else(|| MinigrepError::Argument(format!("missing value for {}", flag)))?;
+            Ok(utf8_arg(value.as_ref())?.to_string())
+        }
+    }
+}
+
+// 选项、选项的值和查询必须是 UTF-8，只有文件路径可以是任意字节
+fn utf8_arg(arg: &OsStr) -> Result<&str, MinigrepError> {
+    arg.to_str()
+        .ok_or_else(|| MinigrepError::Argument(format!("argument is not valid UTF-8: {}", arg.to_string_lossy())))
+}
+
+// 第一个位置参数是 index 时是建立索引的子命令，前面可以有全局选项，比如 `minigrep --no-config index DIR`
+fn is_index_command<S: AsRef<OsStr>>(args: &[S]) -> bool {
+    let mut args = args.iter().map(AsRef::as_ref);
+    while let Some(arg) = args.next() {
+        if arg == "--" || arg == "-" || !arg.as_encoded_bytes().starts_with(b"-") {
+            return arg == "index";
+        }
+        // 跳过选项的值，比如 --sort index 中的 index
+        if let Some((option, None)) = arg.to_str().and_then(options::lookup) {
+            if option.value.is_some() {
+                args.next();
+            }
+        }
+    }
+    false
+}
+
+fn number_error(flag: &str, value: &str) -> MinigrepError {
+    MinigrepError::Argument(format!("{} expects a number, got {}", flag, value))
+}
+
+// run 的结果，main 根据它决定退出码：和 grep 一样，0 表示有匹配，1 表示没有匹配，2 表示出错
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Matched,
+    NoMatch,
+    // 有文件读取失败，其它文件仍然搜索过了
+    HadErrors,
+}
+
+impl From<bool> for Outcome {
+    fn from(matched: bool) -> Outcome {
+        if matched {
+            Outcome::Matched
+        } else {
+            Outcome::NoMatch
+        }
+    }
+}
+
+pub fn run(mut config: Config) -> Result<Outcome, MinigrepError> {
+    if let Some(what) = config.generate {
+        // 和搜索结果一样写到锁住的 stdout，管道被关闭时返回错误而不是像 print! 那样 panic
+        io::stdout().lock().write_all(options::generate(what).as_bytes())?;
+        return Ok(Outcome::Matched);
+    }
+    // 解析参数时不访问文件系统，用到文件时才决定；归档要读了开头才知道，在 search_archive 中决定
+    config.with_filename = has_many_files(&config.file_paths);
+
+    if config.interactive {
+        return tui::run(&config);
+    }
+
+    if config.watch {
+        return watch::run(&config);
+    }
+    if config.follow {
+        return follow::run(&config);
+    }
+
+    if config.build_index {
+        for dir in &config.file_paths {
+            let (files, updated) = index::build(dir)?;
+            println!("indexed {} files in {} ({} updated)", files, dir.display(), updated);
+        }
+        return Ok(Outcome::Matched);
+    }
+
+    let mut stats = Stats::default();
+    let mut errors = Vec::new();
+    let started = Instant::now();
+    let mut files = if config.use_index {
+        index::candidate_files(&config)?
+    } else {
+        walk_paths(&config.file_paths, &mut errors)
+    };
+    if let Some(key) = config.sort {
+        sort::sort_files(&mut files, key, config.sort_reverse);
+    }
+    stats.collect_time = started.elapsed();
+
+    let mut messages = io::stderr();
+    let mut had_errors = !errors.is_empty();
+    for error in errors {
+        warn(&config, &error, &mut messages);
+    }
+
+    let started = Instant::now();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    had_errors |= search_files(&config, files, &mut stats, &mut out, &mut messages)?;
+    stats.search_time = started.elapsed();
+
+    if config.stats {
+        out.flush()?;
+        stats.print(&mut messages)?;
+    }
+
+    Ok(outcome(&config, stats.files_matched > 0, had_errors))
+}
+
+// 搜索结束时的结果，-q 找到匹配时和 grep 一样忽略其它文件的错误
+fn outcome(config: &Config, matched: bool, had_errors: bool) -> Outcome {
+    if config.quiet && matched {
+        Outcome::Matched
+    } else if had_errors {
+        Outcome::HadErrors
+    } else {
+        Outcome::from(matched)
+    }
+}
+
+// 依次搜索所有文件，某个文件出错时输出提示并继续搜索其它文件，返回是否有文件出错。
+// 和具体文件无关的错误（比如写入标准输出失败）会直接返回
+fn search_files(
+    config: &Config,
+    files: Vec<PathBuf>,
+    stats: &mut Stats,
+    out: &mut impl Write,
+    messages: &mut impl Write,
+) -> Result<bool, MinigrepError> {
+    let mut had_errors = false;
+    // --unique 需要看完所有文件才知道每一行出现了几次，所以先收集起来最后再输出
+    let mut unique = Unique::default();
+
+    for path in files {
+        let found = if config.unique {
+            search_file(config, &path, &mut unique)
+        } else {
+            search_file(config, &path, out)
         };
+        match found {
+            Ok(file_stats) => stats.add(file_stats),
+            Err(error) if error.path().is_some() => {
+                had_errors = true;
+                warn(config, &error, messages);
+            }
+            Err(error) => return Err(error),
+        }
+        // -q 只关心有没有匹配，找到第一个就可以结束了
+        if config.quiet && stats.files_matched > 0 {
+            break;
+        }
+    }
 
+    if config.unique {
+        unique.print(config.unique_count, out)?;
+    }
+    Ok(had_errors)
+}
 
-        Ok(Config {query, file_path, ignore_case})
+// 输出某个文件出错的提示，--no-messages 时不输出
+fn warn(config: &Config, error: &MinigrepError, messages: &mut impl Write) {
+    if !config.no_messages {
+        let _ = writeln!(messages, "minigrep: {}", display_chain(error));
     }
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.file_path)?;  // 本应能够读取文件
+// 搜索多个文件或目录时在每行结果前面加上文件名
+fn has_many_files(paths: &[PathBuf]) -> bool {
+    paths.len() > 1 || paths.iter().any(|path| path.is_dir())
+}
+
+// 把目录展开成其中的所有文件，目录中以 . 开头的隐藏文件和目录会被跳过，遇到错误时直接返回
+fn collect_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, MinigrepError> {
+    let mut errors = Vec::new();
+    let files = walk_paths(paths, &mut errors);
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(files),
+    }
+}
+
+// 和 collect_files 一样，但是无法读取的目录会被记录下来并跳过，其它目录照常展开
+fn walk_paths(paths: &[PathBuf], errors: &mut Vec<MinigrepError>) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            walk_dir(path, &mut files, errors);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    files
+}
+
+fn walk_dir(dir: &Path, files: &mut Vec<PathBuf>, errors: &mut Vec<MinigrepError>) {
+    // 按文件名排序，保证每次输出的顺序一样
+    let mut entries: Vec<_> = match fs::read_dir(dir).and_then(|entries| entries.collect::<io::Result<_>>()) {
+        Ok(entries) => entries,
+        Err(e) => return errors.push(MinigrepError::io(dir)(e)),
+    };
+    entries.sort_by_key(|entry| entry.file_name());
 
-    let results = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents)
+    for entry in entries {
+        if entry.file_name().as_encoded_bytes().starts_with(b".") {
+            continue;
+        }
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => walk_dir(&path, files, errors),
+            Ok(_) => files.push(path),
+            Err(e) => errors.push(MinigrepError::io(&path)(e)),
+        }
+    }
+}
+
+// -q 和 -l 只需要知道有没有匹配，所以每个文件最多只找一个
+fn match_limit(config: &Config) -> usize {
+    if config.quiet || config.files_with_matches {
+        1
     } else {
-        search(&config.query, &contents)
+        config.max_count.unwrap_or(usize::MAX)
+    }
+}
+
+fn search_file(config: &Config, path: &Path, out: &mut impl Write) -> Result<FileStats, MinigrepError> {
+    // 预处理器的输出是文本，不再当作归档或压缩文件处理
+    if let Some(command) = preprocess::command(config, path) {
+        let bytes = preprocess::run(command, path)?;
+        return search_contents(config, path, &bytes, out);
+    }
+
+    // 文件只打开一次，判断是不是归档时只看缓冲区中的开头，不会读走数据，管道中的内容也不会丢
+    let mut reader = BufReader::new(File::open(path).map_err(MinigrepError::io(path))?);
+    if config.search_archives && archive::is_archive(reader.fill_buf().map_err(MinigrepError::io(path))?, path) {
+        return search_archive(config, path, read_all(reader, path)?, out);
+    }
+
+    // 不认识的语言分不出注释和代码，不读文件直接跳过
+    if config.scope.is_some() && !lexer::supports(path) {
+        return Ok(FileStats::default());
+    }
+
+    // 多行模式、压缩文件和区分注释、字符串都需要一次读入整个文件
+    if config.multiline || config.search_compressed || config.scope.is_some() {
+        let bytes = decode(config, path, read_all(reader, path)?)?;
+        return search_contents(config, path, &bytes, out);
+    }
+
+    // 其余情况逐行读取文件，找够结果之后就不再继续读了。
+    // 按字节读取，这样不是 UTF-8 的文件也能搜索，输出时再把无效的字节替换掉
+    let ignore_case = config.case_mode.ignore_case(&config.query);
+    let matcher = line_matcher(config, &config.query, ignore_case)?;
+    // 有 --since 时直接跳到范围开始的地方，需要行号时才去数跳过了多少行。
+    // 管道等不能移动读取位置的输入从头读，范围之前的行由 timerange::filter 跳过
+    let seekable = reader.get_ref().metadata().is_ok_and(|metadata| metadata.is_file());
+    let skipped = match &config.time_range {
+        Some(range) if seekable => range.seek_since(&mut reader, config.line_number).map_err(MinigrepError::io(path))?,
+        _ => 0,
     };
+    // 读过的字节数自己数，文件可能是管道，不能问它的位置
+    let mut bytes_searched = 0;
+    let lines = read_byte_lines(&mut reader, &mut bytes_searched).enumerate();
+    let hits = timerange::filter(config.time_range.as_ref(), lines, |(_, line)| line.as_deref().ok())
+        .filter_map(|(i, line)| match line {
+            Ok(line) => line_match(config, &matcher, &line)
+                .map(|found| Ok((skipped + i + 1, String::from_utf8_lossy(&line).into_owned(), found.span))),
+            Err(e) => Some(Err(e)),
+        })
+        .take(match_limit(config));
+
+    let lines_matched = report(config, path, hits, out)?;
+
+    Ok(FileStats { lines_matched, bytes_searched })
+}
+
+// 在已经读入内存的内容中搜索，path 只用于输出
+fn search_contents(config: &Config, path: &Path, bytes: &[u8], out: &mut impl Write) -> Result<FileStats, MinigrepError> {
+    let ignore_case = config.case_mode.ignore_case(&config.query);
+
+    if config.multiline {
+        // 多行模式在整个内容上匹配，不是 UTF-8 的字节会先被替换掉
+        let contents = String::from_utf8_lossy(bytes);
+        let query = unescape(&config.query);
+        // -m 限制的是输出的行数，一个匹配的块可能包含多行
+        let hits = search_multiline(&query, &contents, ignore_case)
+            .into_iter()
+            .flat_map(|block| block.lines())
+            .take(match_limit(config))
+            .map(|(number, line)| Ok((number, line, None)));
+        return Ok(FileStats {
+            lines_matched: report(config, path, hits, out)?,
+            bytes_searched: bytes.len() as u64,
+        });
+    }
+
+    let matcher = line_matcher(config, &config.query, ignore_case)?;
+    // 在去掉了范围之外内容的副本上匹配，输出原来的行。归档中不认识的语言的文件在这里跳过
+    let masked = match config.scope.map(|scope| lexer::mask(bytes, path, scope)) {
+        Some(None) => return Ok(FileStats::default()),
+        masked => masked.flatten(),
+    };
+    let lines = byte_lines(bytes).zip(byte_lines(masked.as_deref().unwrap_or(bytes))).enumerate();
+    let hits = timerange::filter(config.time_range.as_ref(), lines, |&(_, (line, _))| Some(line))
+        .filter_map(|(i, (line, scoped))| {
+            let found = line_match(config, &matcher, scoped)?;
+            // 副本中每个字节的位置和原来的行一样，位置可以直接用。
+            // 只有不是 UTF-8 的行替换无效字节之后两边的位置会不一样，这时不报告位置
+            let span = found.span.filter(|_| scoped == line || std::str::from_utf8(line).is_ok());
+            Some(Ok((i + 1, String::from_utf8_lossy(line), span)))
+        })
+        .take(match_limit(config));
+    Ok(FileStats {
+        lines_matched: report(config, path, hits, out)?,
+        bytes_searched: bytes.len() as u64,
+    })
+}
+
+// 依次搜索归档中的每个文件，结果中的路径写成 archive.zip!/path/in/archive
+fn search_archive(config: &Config, path: &Path, bytes: Vec<u8>, out: &mut impl Write) -> Result<FileStats, MinigrepError> {
+    // 归档和目录一样包含多个文件，只搜索一个归档时也要输出文件名
+    let config = &Config { with_filename: true, ..config.clone() };
+    let mut stats = FileStats::default();
+
+    for member in archive::members(bytes).map_err(MinigrepError::encoding(path))? {
+        let mut member_path = path.as_os_str().to_owned();
+        member_path.push("!/");
+        member_path.push(&member.path);
+        let member_path = PathBuf::from(member_path);
+        let data = if config.search_compressed {
+            decompress::decompress(member.data).map_err(MinigrepError::encoding(&member_path))?
+        } else {
+            member.data
+        };
+        let found = search_contents(config, &member_path, &data, out)?;
+        stats.lines_matched += found.lines_matched;
+        stats.bytes_searched += found.bytes_searched;
+
+        if config.quiet && stats.lines_matched > 0 {
+            break;
+        }
+    }
+
+    Ok(stats)
+}
+
+// 逐行读取字节，去掉行尾的 \n 或 \r\n，读到的字节数累加到 read
+fn read_byte_lines<'a>(
+    mut reader: impl BufRead + 'a,
+    read: &'a mut u64,
+) -> impl Iterator<Item = io::Result<Vec<u8>>> + 'a {
+    std::iter::from_fn(move || {
+        let mut line = Vec::new();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => None,
+            Ok(n) => {
+                *read += n as u64;
+                if line.ends_with(b"\n") {
+                    line.pop();
+                    if line.ends_with(b"\r") {
+                        line.pop();
+                    }
+                }
+                Some(Ok(line))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    })
+}
+
+// 判断一行是否匹配，指定了 --field 时只看这一列，没有这一列的行不匹配
+fn line_matches(config: &Config, matcher: &Matcher, line: &[u8]) -> bool {
+    line_match(config, matcher, line).is_some()
+}
+
+// 和 line_matches 一样，同时返回匹配的位置。--field 时的位置是相对于这一列的，换算不回整行，所以不报告
+fn line_match(config: &Config, matcher: &Matcher, line: &[u8]) -> Option<LineMatch> {
+    match config.field {
+        Some(n) => field::field(line, config.delimiter, n)
+            .and_then(|field| matcher.find_bytes(&field))
+            .map(|_| LineMatch { span: None }),
+        None => matcher.find_bytes(line),
+    }
+}
+
+fn line_matcher(config: &Config, query: &str, ignore_case: bool) -> Result<Matcher, MinigrepError> {
+    if config.expr {
+        // 每个词单独决定是否忽略大小写，--smart-case 时只有含大写字母的词区分大小写
+        let expr = expr::parse(query)?
+            .try_map(&mut |term: String| term_matcher(config, &term, config.case_mode.ignore_case(&term)))?;
+        return Ok(Matcher::Expr(Box::new(expr)));
+    }
+    term_matcher(config, query, ignore_case)
+}
+
+fn term_matcher(config: &Config, query: &str, ignore_case: bool) -> Result<Matcher, MinigrepError> {
+    Ok(match config.fuzzy {
+        Some(distance) => Matcher::Fuzzy(FuzzyMatcher::new(query, distance, ignore_case)?),
+        None => Matcher::Literal(LineMatcher::new(query, ignore_case)),
+    })
+}
+
+// 输出一个文件的匹配结果，返回匹配的行数，迭代器是惰性的，提前结束循环就不会再继续搜索。
+// 每一项是 (行号, 内容, 最佳匹配的字节范围)
+fn report<S: AsRef<str>>(
+    config: &Config,
+    path: &Path,
+    hits: impl Iterator<Item = io::Result<(usize, S, Option<(usize, usize)>)>>,
+    out: &mut impl Write,
+) -> Result<usize, MinigrepError> {
+    let mut matched = 0;
+    let highlight = highlights(config);
+
+    for hit in hits {
+        // 读取文件出错时带上文件的路径，写入输出出错时没有路径
+        let (number, line, span) = hit.map_err(MinigrepError::io(path))?;
+        matched += 1;
+
+        if config.quiet {
+            break;
+        }
+        if config.files_with_matches {
+            out.write_all(&config.display_path(path))?;
+            out.write_all(if config.null { b"\0" } else { b"\n" })?;
+            break;
+        }
+        let line = line.as_ref();
+        // --column 总是报告位置，颜色只在终端中使用
+        let column = span.map(|(start, _)| start + 1);
+        match span {
+            Some((start, end)) if highlight => {
+                let marked = format!("{}\x1b[1;31m{}\x1b[0m{}", &line[..start], &line[start..end], &line[end..]);
+                print_line(config, path, number, column, &marked, out)?;
+            }
+            _ => print_line(config, path, number, column, line, out)?,
+        }
+    }
+
+    Ok(matched)
+}
+
+// 只有输出到终端时才高亮匹配的部分，避免颜色代码混进管道里。
+// --unique 按原始的行去重，同一行在不同的文件中高亮的位置可能不同（比如 --code-only），所以不加颜色
+fn highlights(config: &Config) -> bool {
+    !config.unique && io::stdout().is_terminal()
+}
+
+fn print_line(
+    config: &Config,
+    path: &Path,
+    number: usize,
+    column: Option<usize>,
+    line: &str,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    // --unique 按行的内容去重，加上文件名和行号之后每一行都不一样了
+    if config.unique {
+        return writeln!(out, "{}", line);
+    }
+
+    // 文件名按原来的字节输出，所以前缀是字节而不是字符串
+    let mut prefix = Vec::new();
+    if config.with_filename {
+        prefix.extend_from_slice(&config.display_path(path));
+        prefix.push(if config.null { b'\0' } else { b':' });
+    }
+    if config.line_number {
+        write!(prefix, "{}:", number)?;
+    }
+    if let Some(column) = column.filter(|_| config.column) {
+        write!(prefix, "{}:", column)?;
+    }
+
+    out.write_all(&prefix)?;
+    writeln!(out, "{}", line)
+}
+
+// 路径原来的字节。其它系统上的路径不一定能表示成字节，只能把无效的部分替换掉
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        Cow::Borrowed(path) => Cow::Borrowed(path.as_bytes()),
+        Cow::Owned(path) => Cow::Owned(path.into_bytes()),
+    }
+}
+
+fn read_bytes(config: &Config, path: &Path) -> Result<Vec<u8>, MinigrepError> {
+    if let Some(command) = preprocess::command(config, path) {
+        return preprocess::run(command, path);
+    }
+
+    let bytes = fs::read(path).map_err(MinigrepError::io(path))?;
+    decode(config, path, bytes)
+}
 
-    for line in results {
-        println!("{}", line);
+fn read_all(mut input: impl Read, path: &Path) -> Result<Vec<u8>, MinigrepError> {
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes).map_err(MinigrepError::io(path))?;
+    Ok(bytes)
+}
+
+// 压缩文件先解压，再按普通文件搜索
+fn decode(config: &Config, path: &Path, bytes: Vec<u8>) -> Result<Vec<u8>, MinigrepError> {
+    if !config.search_compressed {
+        return Ok(bytes);
     }
+    decompress::decompress(bytes).map_err(MinigrepError::encoding(path))
+}
 
-    Ok(())
+// 需要字符串的地方（比如交互模式）把不是 UTF-8 的字节替换掉
+fn read_contents(config: &Config, path: &Path) -> Result<String, MinigrepError> {
+    let bytes = read_bytes(config, path)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
 }
 
 // 打印命令行参数和环境变量信息
@@ -71,7 +1032,10 @@ pub fn print_startup_info() {
     // 获取环境变量IGNORE_CASE的值
     let ignore_case_value = env::var("IGNORE_CASE").unwrap_or_else(|_| "not set".to_string());
     // 获取命令行参数并将其组合成一个字符串
-    let command_line = env::args().collect::<Vec<String>>().join(" ");
+    let command_line = env::args_os()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect::<Vec<String>>()
+        .join(" ");
 
     // 打印命令和环境变量信息到标准输出和标准错误
     println!("Running command: {}", command_line);
@@ -84,6 +1048,7 @@ pub fn print_startup_info() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{output, Temp};
 
     #[test]
     fn one_result() {
@@ -120,5 +1085,265 @@ Trust me.";
     
         assert_eq!(vec!["Rust:", "Trust me."], search_case_insensitive(query, contents));
     }
-}
 
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn config_precedence() {
+        let defaults = args(&["--ignore-case"]);
+
+        // 只有配置文件
+        let config = Config::build_from(&args(&["minigrep", "to", "poem.txt"]), &defaults, None).unwrap();
+        assert_eq!(CaseMode::Insensitive, config.case_mode);
+
+        // 环境变量覆盖配置文件
+        let config = Config::build_from(&args(&["minigrep", "to", "poem.txt"]), &defaults, Some(false)).unwrap();
+        assert_eq!(CaseMode::Sensitive, config.case_mode);
+
+        // 命令行参数覆盖环境变量
+        let config = Config::build_from(&args(&["minigrep", "-i", "to", "poem.txt"]), &[], Some(false)).unwrap();
+        assert_eq!(CaseMode::Insensitive, config.case_mode);
+    }
+
+    #[test]
+    fn config_rejects_bad_input() {
+        assert!(config_file::parse_bool("maybe").is_err());
+        assert!(Config::build_from(&args(&["minigrep", "to", "poem.txt"]), &args(&["poem.txt"]), None).is_err());
+        assert!(Config::build_from(&args(&["minigrep", "--bogus", "to", "poem.txt"]), &[], None).is_err());
+    }
+
+    #[test]
+    fn errors_carry_details() {
+        let error = Config::build_from(&args(&["minigrep", "--bogus", "to", "poem.txt"]), &[], None).err().unwrap();
+        assert!(matches!(&error, MinigrepError::Argument(message) if message == "unknown flag: --bogus"));
+        let error = Config::build_from(&args(&["minigrep", "--fuzzy=1", &"x".repeat(65), "poem.txt"]), &[], None).err().unwrap();
+        assert!(matches!(error, MinigrepError::Pattern(_)));
+
+        let config = Config::build_from(&args(&["minigrep", "to", "missing.txt"]), &[], None).unwrap();
+        let error = search_file(&config, Path::new("missing.txt"), &mut Vec::new()).err().unwrap();
+        assert!(matches!(&error, MinigrepError::Io { path: Some(path), source }
+            if path == "missing.txt" && source.kind() == io::ErrorKind::NotFound));
+
+        let corrupt = Temp::file("corrupt.gz", b"\x1f\x8b\x08\x00 not really gzip");
+        let path = corrupt.path();
+        let config = Config::build_from(&args(&["minigrep", "-z", "to", &path]), &[], None).unwrap();
+        let error = search_file(&config, &corrupt, &mut Vec::new()).err().unwrap();
+        assert!(matches!(&error, MinigrepError::Encoding { path: bad, .. } if *bad == path));
+    }
+
+    #[test]
+    fn continue_after_errors() {
+        let dir = Temp::dir("errors");
+        fs::write(dir.join("rust.txt"), "Rust:\nsafe, fast, productive.\n").unwrap();
+        let root = dir.path();
+
+        let mut config = Config::build_from(&args(&["minigrep", "nobody", "poem.txt", "missing.txt", &root]), &[], None).unwrap();
+        config.with_filename = has_many_files(&config.file_paths);
+        let mut errors = Vec::new();
+        let files = walk_paths(&config.file_paths, &mut errors);
+        assert!(errors.is_empty());
+
+        let mut stats = Stats::default();
+        let (mut out, mut messages) = (Vec::new(), Vec::new());
+        assert!(search_files(&config, files.clone(), &mut stats, &mut out, &mut messages).unwrap());
+        // missing.txt 之后的文件仍然被搜索了
+        assert_eq!(files.len() - 1, stats.files_searched);
+        assert_eq!("poem.txt:I'm nobody! Who are you?\npoem.txt:Are you nobody, too?\n", String::from_utf8(out).unwrap());
+        assert_eq!(
+            "minigrep: missing.txt: No such file or directory (os error 2)\n",
+            String::from_utf8(messages).unwrap()
+        );
+
+        let config = Config::build_from(&args(&["minigrep", "--no-messages", "nobody", "missing.txt"]), &[], None).unwrap();
+        let mut messages = Vec::new();
+        assert!(search_files(&config, config.file_paths.clone(), &mut Stats::default(), &mut Vec::new(), &mut messages).unwrap());
+        assert!(messages.is_empty());
+    }
+
+    #[test]
+    fn smart_case() {
+        let config = Config::build_from(&args(&["minigrep", "-S", "rust", "poem.txt"]), &[], Some(false)).unwrap();
+        assert!(config.case_mode.ignore_case(&config.query));
+        assert!(!CaseMode::Smart.ignore_case("Rust"));
+        assert!(CaseMode::Smart.ignore_case("ß"));
+    }
+
+    #[test]
+    fn max_count_and_multiple_files() {
+        let config = Config::build_from(&args(&["minigrep", "-m2", "to", "a.txt", "b.txt"]), &[], None).unwrap();
+        assert_eq!(Some(2), config.max_count);
+        assert_eq!(vec![Path::new("a.txt"), Path::new("b.txt")], config.file_paths);
+
+        let config = Config::build_from(&args(&["minigrep", "--max-count=3", "to", "poem.txt", "ig"]), &[], None).unwrap();
+        assert_eq!(Some(3), config.max_count);
+        assert_eq!(CaseMode::Insensitive, config.case_mode);
+        assert!(Config::build_from(&args(&["minigrep", "-m", "many", "to", "poem.txt"]), &[], None).is_err());
+
+        // 多行模式下也按行计数，匹配的块会在中间截断
+        assert_eq!("Are you nobody, too?\n", output(&["-U", "-m1", "too?\\n", "poem.txt"]));
+    }
+
+    #[test]
+    fn sort_and_unique_flags() {
+        let config = Config::build_from(&args(&["minigrep", "--sortr=modified", "--unique-count", "to", "."]), &[], None).unwrap();
+        assert!(!highlights(&config));
+        assert_eq!(Some(SortKey::Modified), config.sort);
+        assert!(config.sort_reverse && config.unique && config.unique_count);
+        assert!(Config::build_from(&args(&["minigrep", "--sort", "size", "to", "."]), &[], None).is_err());
+    }
+
+    // 文件名中的换行符和 / 分隔符只在类 Unix 系统上适用
+    #[cfg(unix)]
+    #[test]
+    fn null_separated_paths() {
+        let dir = Temp::dir("null");
+        fs::create_dir_all(dir.join("with space")).unwrap();
+        fs::write(dir.join("with space/a:b.txt"), "to be\n").unwrap();
+        fs::write(dir.join("new\nline.txt"), "not to\nnothing\n").unwrap();
+        let root = dir.path();
+
+        assert_eq!(
+            format!("{root}/new\nline.txt\0{root}/with space/a:b.txt\0"),
+            output(&["-l", "-0", "to", &root])
+        );
+        assert_eq!(
+            format!("{root}/new\nline.txt\0not to\n{root}/with space/a:b.txt\0to be\n"),
+            output(&["--null", "to", &root])
+        );
+
+        let slashes = root.replace('/', "\\");
+        assert_eq!(
+            format!("{slashes}\\new\nline.txt\n{slashes}\\with space\\a:b.txt\n"),
+            output(&["-l", "--path-separator=\\", "to", &root])
+        );
+        assert!(Config::build_from(&args(&["minigrep", "--path-separator", "//", "to", "."]), &[], None).is_err());
+    }
+
+    #[test]
+    fn search_archive_members() {
+        // 只搜索一个归档时也输出文件名
+        let members = |path: &str| output(&["--search-archives", "-n", "Rust", path]);
+        assert_eq!("tests/fixtures/bundle.zip!/rust.txt:1:Rust:\n", members("tests/fixtures/bundle.zip"));
+        let long = format!("src/{}rust.txt", "very_long_directory_name/".repeat(5));
+        for path in ["tests/fixtures/bundle.tar", "tests/fixtures/bundle.tar.gz"] {
+            assert_eq!(format!("{}!/{}:1:Rust:\n", path, long), members(path));
+        }
+    }
+
+    #[test]
+    fn field_restricted_search() {
+        let csv = Temp::file(
+            "field.csv",
+            "time,level,message\n\
+             09:00,ERROR,\"disk full, retrying\"\n\
+             09:01,INFO,\"ERROR count reset\"\n\
+             09:02,ERROR\n",
+        );
+        let path = csv.path();
+
+        // 第 3 行的 ERROR 在第 3 列，不算匹配
+        assert_eq!("09:00,ERROR,\"disk full, retrying\"\n09:02,ERROR\n", output(&["--field", "2", "ERROR", &path]));
+        // 引号中的逗号不是分隔符
+        assert_eq!("2:09:00,ERROR,\"disk full, retrying\"\n", output(&["--field=3", "-n", "full, r", &path]));
+        assert_eq!("", output(&["--field", "3", "--delimiter", "\\t", "full", &path]));
+
+        assert!(Config::build_from(&args(&["minigrep", "--field", "0", "to", "poem.txt"]), &[], None).is_err());
+        assert!(Config::build_from(&args(&["minigrep", "--field", "1", "-U", "to", "poem.txt"]), &[], None).is_err());
+    }
+
+    #[test]
+    fn time_range_search() {
+        let log = Temp::file(
+            "since.log",
+            "2024-05-01 09:00:00 ERROR early\n\
+             2024-05-01 10:00:00 ERROR disk full\n\
+             \x20 ERROR in stack trace\n\
+             2024-05-01 11:00:00 INFO ok\n\
+             2024-05-01 12:00:00 ERROR late\n",
+        );
+        let path = log.path();
+        let errors = |flags: &[&str]| {
+            let mut list = vec!["--time-format", "%Y-%m-%d %H:%M:%S"];
+            list.extend_from_slice(flags);
+            list.extend_from_slice(&["ERROR", &path]);
+            output(&list)
+        };
+
+        // 跳过前面的行之后行号仍然正确，没有时间戳的行跟着前一行
+        assert_eq!(
+            "2:2024-05-01 10:00:00 ERROR disk full\n3:  ERROR in stack trace\n",
+            errors(&["-n", "--since", "2024-05-01 10", "--until", "2024-05-01 12"])
+        );
+        assert_eq!("2024-05-01 12:00:00 ERROR late\n", errors(&["--since", "2024-05-01 11:30"]));
+        assert_eq!("2024-05-01 09:00:00 ERROR early\n", errors(&["--until", "2024-05-01 10"]));
+        // 压缩文件等需要整个读入的内容也会过滤，只是不能二分查找
+        assert_eq!("2024-05-01 12:00:00 ERROR late\n", errors(&["-z", "--since", "2024-05-01 11:30"]));
+
+        assert!(Config::build_from(&args(&["minigrep", "--since", "May 1", "to", "poem.txt"]), &[], None).is_err());
+        assert!(Config::build_from(&args(&["minigrep", "--until", "2024", "-U", "to", "poem.txt"]), &[], None).is_err());
+    }
+
+    #[test]
+    fn code_and_comment_scopes() {
+        let source = Temp::file(
+            "scope.rs",
+            "// 读取 config\n\
+             let config = load(\"config.toml\");\n\
+             /* 多行注释里的\n   config */\n\
+             println!(\"{}\", config.name); // 输出 config\n",
+        );
+        let path = source.path();
+
+        assert_eq!(
+            "2:let config = load(\"config.toml\");\n5:println!(\"{}\", config.name); // 输出 config\n",
+            output(&["-n", "--code-only", "config", &path])
+        );
+        assert_eq!(
+            "1:// 读取 config\n4:   config */\n5:println!(\"{}\", config.name); // 输出 config\n",
+            output(&["-n", "--comments-only", "config", &path])
+        );
+        // 位置来自去掉了注释的副本，和原来的行一致
+        assert!(output(&["--column", "--comments-only", "config", &path])
+            .ends_with("\n40:println!(\"{}\", config.name); // 输出 config\n"));
+
+        // 不认识的语言直接跳过
+        assert_eq!("", output(&["--code-only", "to", "poem.txt"]));
+
+        assert!(Config::build_from(&args(&["minigrep", "--code-only", "-U", "to", "poem.txt"]), &[], None).is_err());
+    }
+
+    #[test]
+    fn expr_search() {
+        assert_eq!("I'm nobody! Who are you?\n", output(&["--expr", "nobody & !too", "poem.txt"]));
+        assert_eq!(
+            "Then there's a pair of us - don't tell!\nTo tell your name the livelong day\nTo an admiring bog!\n",
+            output(&["--expr", "tell | (bog & admiring)", "poem.txt"])
+        );
+        // 每个词单独按智能大小写处理
+        assert_eq!(
+            "I'm nobody! Who are you?\nAre you nobody, too?\n",
+            output(&["-S", "--expr", "NOBODY | are", "poem.txt"])
+        );
+
+        let error = match Config::build_from(&args(&["minigrep", "--expr", "nobody & (too", "poem.txt"]), &[], None) {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("unbalanced parenthesis accepted"),
+        };
+        assert!(error.contains("column 14"), "{}", error);
+    }
+
+    #[test]
+    fn search_is_lazy() {
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+        let mut results = search_numbered("rust", contents, true);
+        assert_eq!(Some((1, "Rust:")), results.next());
+        assert_eq!(vec![(4, "Trust me.")], results.collect::<Vec<_>>());
+    }
+}
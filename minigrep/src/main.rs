@@ -3,27 +3,37 @@
 // - 初始化其它配置
 // - 调用 `lib.rs` 中的 `run` 函数，以启动逻辑代码的运行
 // - 如果 `run` 返回一个错误，需要对该错误进行处理
+// - 退出码和 grep 一致：0 表示有匹配，1 表示没有匹配，2 表示出错
 
 use std::env;
+use std::ffi::OsString;
 use std::process;
 
-use minigrep::Config;
+use minigrep::{display_chain, Config, Outcome};
 use minigrep::print_startup_info;
 
 fn main() {
-    print_startup_info();
-
-    let args: Vec<String> = env::args().collect();
+    // 用 args_os 而不是 args，文件名不是 UTF-8 时不会 panic
+    let args: Vec<OsString> = env::args_os().collect();
 
     let config = Config::build(&args).unwrap_or_else(|err| {
-        eprintln!("problem parsing arguments: {err}");
-        process::exit(1);
+        eprintln!("problem parsing arguments: {}", display_chain(&err));
+        process::exit(2);
     });
 
-    println!("Searching for \"{}\" in file {}: ", config.query, config.file_path);
+    if config.show_banner() {
+        print_startup_info();
+        let paths: Vec<String> = config.file_paths.iter().map(|path| path.display().to_string()).collect();
+        println!("Searching for \"{}\" in file {}: ", config.query, paths.join(", "));
+    }
 
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("Application error: {e}");
-        process::exit(1);
+    match minigrep::run(config) {
+        Ok(Outcome::Matched) => {}
+        Ok(Outcome::NoMatch) => process::exit(1),
+        Ok(Outcome::HadErrors) => process::exit(2),
+        Err(e) => {
+            eprintln!("Application error: {}", display_chain(&e));
+            process::exit(2);
+        }
     }
 }
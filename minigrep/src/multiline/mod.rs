@@ -0,0 +1,176 @@
+// 多行模式：在整个文件内容上匹配查询，一个匹配可以跨越多行
+
+// 一个匹配结果覆盖的所有整行
+#[derive(Debug, PartialEq, Eq)]
+pub struct Block<'a> {
+    // 第一行的行号，从 1 开始
+    pub first_line: usize,
+    // 不包含最后一行的换行符
+    pub text: &'a str,
+}
+
+impl<'a> Block<'a> {
+    pub fn lines(&self) -> impl Iterator<Item = (usize, &'a str)> {
+        let first_line = self.first_line;
+        self.text
+            .lines()
+            .enumerate()
+            .map(move |(i, line)| (first_line + i, line))
+    }
+}
+
+// 把查询中的 \n、\t、\\ 转义成真正的字符，这样在命令行中也能写出换行
+pub fn unescape(query: &str) -> String {
+    let mut result = String::with_capacity(query.len());
+    let mut chars = query.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('\\') => result.push('\\'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+pub fn search_multiline<'a>(query: &str, contents: &'a str, ignore_case: bool) -> Vec<Block<'a>> {
+    // 每一行开始位置的字节偏移
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(contents.match_indices('\n').map(|(i, _)| i + 1))
+        .filter(|&start| start < contents.len())
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+
+    // 每个匹配覆盖的行范围（下标从 0 开始，两端都包含）
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in find_all(query, contents, ignore_case) {
+        let first = line_of(start);
+        let last = line_of(end.max(start + 1) - 1);
+
+        // 和上一个匹配有重叠的行合并成同一个结果，避免重复打印
+        match ranges.last_mut() {
+            Some((_, prev_last)) if first <= *prev_last => *prev_last = (*prev_last).max(last),
+            _ => ranges.push((first, last)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(first, last)| {
+            let start = line_starts[first];
+            let mut end = line_starts.get(last + 1).copied().unwrap_or(contents.len());
+            let text = &contents[start..end];
+            if text.ends_with('\n') {
+                end -= 1;
+                if contents[start..end].ends_with('\r') {
+                    end -= 1;
+                }
+            }
+            Block {
+                first_line: first + 1,
+                text: &contents[start..end],
+            }
+        })
+        .collect()
+}
+
+// 找出所有不重叠的匹配，返回字节偏移范围 [start, end)
+fn find_all(query: &str, contents: &str, ignore_case: bool) -> Vec<(usize, usize)> {
+    if contents.is_empty() {
+        return Vec::new();
+    }
+
+    // 空查询和普通模式一样匹配每一行
+    if query.is_empty() {
+        return std::iter::once(0)
+            .chain(contents.match_indices('\n').map(|(i, _)| i + 1))
+            .filter(|&start| start < contents.len())
+            .map(|start| (start, start))
+            .collect();
+    }
+
+    if !ignore_case {
+        return contents
+            .match_indices(query)
+            .map(|(start, m)| (start, start + m.len()))
+            .collect();
+    }
+
+    // 忽略大小写时不能直接把整个内容转成小写，因为小写后字节偏移可能会变化
+    let needle: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let mut matches = Vec::new();
+    let mut pos = 0;
+
+    while pos < contents.len() {
+        match match_at(contents, pos, &needle) {
+            Some(end) => {
+                matches.push((pos, end));
+                pos = end;
+            }
+            None => pos += contents[pos..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+
+    matches
+}
+
+fn match_at(contents: &str, start: usize, needle: &[char]) -> Option<usize> {
+    let mut want = needle.iter();
+
+    for (i, c) in contents[start..].char_indices() {
+        for lower in c.to_lowercase() {
+            if want.next() != Some(&lower) {
+                return None;
+            }
+        }
+        if want.as_slice().is_empty() {
+            return Some(start + i + c.len_utf8());
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_spans_lines() {
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+
+        let blocks = search_multiline(&unescape("productive.\\nPick"), contents, false);
+        assert_eq!(
+            vec![Block { first_line: 2, text: "safe, fast, productive.\nPick three." }],
+            blocks
+        );
+        assert_eq!(vec![(2, "safe, fast, productive."), (3, "Pick three.")], blocks[0].lines().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn overlapping_lines_are_merged() {
+        let contents = "x\nx\nx\ny\n";
+
+        let blocks = search_multiline("\nx", contents, false);
+        assert_eq!(vec![Block { first_line: 1, text: "x\nx\nx" }], blocks);
+
+        let blocks = search_multiline("X\nY", contents, true);
+        assert_eq!(vec![Block { first_line: 3, text: "x\ny" }], blocks);
+    }
+}
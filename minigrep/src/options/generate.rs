@@ -0,0 +1,186 @@
+// 根据选项表生成 bash、zsh、fish 的补全脚本和 man 手册
+use super::{Generate, OptionSpec, Shell, OPTIONS};
+
+pub fn generate(what: Generate) -> String {
+    match what {
+        Generate::Completions(Shell::Bash) => bash(),
+        Generate::Completions(Shell::Zsh) => zsh(),
+        Generate::Completions(Shell::Fish) => fish(),
+        Generate::Man => man(),
+    }
+}
+
+// 选项的所有写法，比如 ["-m", "--max-count"]
+fn names(option: &OptionSpec) -> Vec<String> {
+    let mut names = Vec::new();
+    if let Some(short) = option.short {
+        names.push(format!("-{}", short));
+    }
+    names.push(format!("--{}", option.long));
+    names
+}
+
+fn bash() -> String {
+    let words: Vec<String> = OPTIONS.iter().flat_map(names).collect();
+
+    let mut script = String::from("_minigrep() {\n");
+    script.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    script.push_str("    case \"$prev\" in\n");
+    for option in OPTIONS.iter().filter(|option| option.value.is_some()) {
+        // 没有固定取值的选项不补全，交给 bash 默认的文件名补全
+        let action = if option.choices.is_empty() {
+            "return".to_string()
+        } else {
+            format!("COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return", option.choices.join(" "))
+        };
+        script.push_str(&format!("        {})\n            {}\n            ;;\n", names(option).join("|"), action));
+    }
+    script.push_str("    esac\n");
+    script.push_str("    if [[ \"$cur\" == -* ]]; then\n");
+    script.push_str(&format!("        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n", words.join(" ")));
+    script.push_str("    fi\n}\n");
+    script.push_str("complete -o default -F _minigrep minigrep\n");
+    script
+}
+
+// zsh 的说明写在单引号和方括号里
+fn zsh_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh() -> String {
+    let mut script = String::from("#compdef minigrep\n\n_arguments -s \\\n");
+    for option in OPTIONS {
+        let help = zsh_escape(option.help);
+        let value = match option.value {
+            Some(name) if option.choices.is_empty() => format!(":{}: ", name),
+            Some(name) => format!(":{}:({})", name, option.choices.join(" ")),
+            None => String::new(),
+        };
+        let line = match (option.short, option.value) {
+            // 短选项的值可以直接跟在后面（-m5），长选项的值可以用 = 连接
+            (Some(short), Some(_)) => format!(
+                "'(-{0} --{1})'{{-{0}+,--{1}=}}'[{2}]{3}'",
+                short, option.long, help, value
+            ),
+            (Some(short), None) => format!("'(-{0} --{1})'{{-{0},--{1}}}'[{2}]'", short, option.long, help),
+            (None, Some(_)) => format!("'--{}=[{}]{}'", option.long, help, value),
+            (None, None) => format!("'--{}[{}]'", option.long, help),
+        };
+        script.push_str(&format!("  {} \\\n", line));
+    }
+    script.push_str("  '1:query: ' \\\n  '*:file:_files'\n");
+    script
+}
+
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish() -> String {
+    let mut script = String::new();
+    for option in OPTIONS {
+        let mut line = String::from("complete -c minigrep");
+        if let Some(short) = option.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        line.push_str(&format!(" -l {}", option.long));
+        if option.value.is_some() {
+            if option.choices.is_empty() {
+                line.push_str(" -r");
+            } else {
+                // -x：必须有值，而且不补全文件名
+                line.push_str(&format!(" -x -a '{}'", option.choices.join(" ")));
+            }
+        }
+        line.push_str(&format!(" -d '{}'\n", fish_escape(option.help)));
+        script.push_str(&line);
+    }
+    script
+}
+
+// roff 中 \ 和 - 需要转义，以 . 或 ' 开头的行会被当作命令
+fn roff_escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with(['.', '\'']) {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+fn man() -> String {
+    let mut page = String::from(
+        ".TH MINIGREP 1\n\
+         .SH NAME\n\
+         minigrep \\- search files for lines containing a query\n\
+         .SH SYNOPSIS\n\
+         .B minigrep\n\
+         [\\fIOPTIONS\\fR] \\fIQUERY\\fR \\fIPATH\\fR...\n\
+         .br\n\
+         .B minigrep index\n\
+         [\\fIDIR\\fR...]\n\
+         .SH DESCRIPTION\n\
+         Print the lines of each \\fIPATH\\fR that contain \\fIQUERY\\fR. \
+         Directories are searched recursively, skipping hidden files.\n\
+         .PP\n\
+         Options are read from the configuration file first, then from the environment, \
+         and finally from the command line, so later sources override earlier ones.\n\
+         .SH OPTIONS\n",
+    );
+
+    for option in OPTIONS {
+        let mut names: Vec<String> = names(option)
+            .iter()
+            .map(|name| format!("\\fB{}\\fR", roff_escape(name)))
+            .collect();
+        if let (Some(value), Some(last)) = (option.value, names.last_mut()) {
+            last.push_str(&format!("=\\fI{}\\fR", value));
+        }
+        page.push_str(&format!(".TP\n{}\n{}\n", names.join(", "), roff_escape(option.help)));
+    }
+
+    page.push_str(
+        ".SH ENVIRONMENT\n\
+         .TP\n\
+         .B IGNORE_CASE\n\
+         Search case-insensitively when set to 1, true, yes or on.\n\
+         .TP\n\
+         .B MINIGREP_CONFIG_PATH\n\
+         Configuration file to read, one option per line. \
+         Defaults to $XDG_CONFIG_HOME/minigrep/config or ~/.config/minigrep/config.\n\
+         .SH EXIT STATUS\n\
+         0 if a line matched, 1 if nothing matched, 2 if an error occurred.\n",
+    );
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_files_cover_every_option() {
+        let bash = generate(Generate::Completions(Shell::Bash));
+        let zsh = generate(Generate::Completions(Shell::Zsh));
+        let fish = generate(Generate::Completions(Shell::Fish));
+        let man = generate(Generate::Man);
+
+        for option in OPTIONS {
+            let long = format!("--{}", option.long);
+            assert!(bash.contains(&format!(" {}", long)), "bash is missing {}", long);
+            assert!(zsh.contains(&long), "zsh is missing {}", long);
+            assert!(fish.contains(&format!(" -l {} ", option.long)), "fish is missing {}", long);
+            assert!(man.contains(&roff_escape(&long)), "man page is missing {}", long);
+        }
+
+        assert!(bash.contains("        --sort)\n            COMPREPLY=($(compgen -W \"path modified created\" -- \"$cur\")); return\n"));
+        assert!(zsh.contains("'(-m --max-count)'{-m+,--max-count=}'[Stop after NUM matching lines per file]:NUM: '"));
+        assert!(fish.contains("complete -c minigrep -l sort -x -a 'path modified created'"));
+        assert!(man.contains(".TP\n\\fB\\-m\\fR, \\fB\\-\\-max\\-count\\fR=\\fINUM\\fR\n"));
+    }
+}
@@ -0,0 +1,169 @@
+// 所有命令行选项的定义。Config::apply_args 按这张表解析参数，
+// 补全脚本和 man 手册也从这张表生成，所以它们不会和实际支持的选项不一致
+use crate::MinigrepError;
+
+mod generate;
+pub use generate::generate;
+
+pub struct OptionSpec {
+    pub short: Option<char>,
+    // 不带 -- 的长选项名
+    pub long: &'static str,
+    // 需要值的选项，值在帮助信息中显示的名字
+    pub value: Option<&'static str>,
+    // 值只能从这些中选择，补全时使用
+    pub choices: &'static [&'static str],
+    pub help: &'static str,
+}
+
+const fn flag(short: Option<char>, long: &'static str, help: &'static str) -> OptionSpec {
+    OptionSpec { short, long, value: None, choices: &[], help }
+}
+
+const fn valued(short: Option<char>, long: &'static str, value: &'static str, help: &'static str) -> OptionSpec {
+    OptionSpec { short, long, value: Some(value), choices: &[], help }
+}
+
+const SORT_KEYS: &[&str] = &["path", "modified", "created"];
+
+pub const OPTIONS: &[OptionSpec] = &[
+    flag(Some('i'), "ignore-case", "Search case-insensitively"),
+    flag(Some('s'), "case-sensitive", "Search case-sensitively (the default)"),
+    flag(Some('S'), "smart-case", "Ignore case unless the query contains an uppercase letter"),
+    flag(Some('z'), "search-zip", "Decompress gzip files before searching"),
+    flag(None, "search-archives", "Search the members of tar and zip archives"),
+    flag(Some('U'), "multiline", "Let the query span lines; \\n in the query matches a newline"),
+    flag(Some('n'), "line-number", "Prefix each match with its line number"),
+    flag(None, "column", "Prefix each match with the byte column (from 1) where the best match starts"),
+    valued(Some('m'), "max-count", "NUM", "Stop after NUM matching lines per file"),
+    flag(Some('q'), "quiet", "Print nothing; report matches through the exit status"),
+    flag(Some('l'), "files-with-matches", "Print only the names of files with matches"),
+    valued(None, "fuzzy", "N", "Match lines within edit distance N of the query"),
+    flag(None, "interactive", "Browse results and refine the query in the terminal"),
+    flag(None, "watch", "Search again whenever the files change"),
+    flag(None, "follow", "Keep reading lines appended to the files"),
+    flag(None, "index", "Use trigram indexes built by `minigrep index`"),
+    flag(None, "stats", "Print a summary of the search to stderr"),
+    OptionSpec {
+        choices: SORT_KEYS,
+        ..valued(None, "sort", "KEY", "Search files in order of KEY")
+    },
+    OptionSpec {
+        choices: SORT_KEYS,
+        ..valued(None, "sortr", "KEY", "Search files in reverse order of KEY")
+    },
+    flag(None, "unique", "Print each distinct matching line once"),
+    flag(None, "unique-count", "Like --unique, prefixing each line with its number of occurrences"),
+    flag(Some('0'), "null", "Terminate file names with NUL instead of a newline or colon"),
+    valued(None, "path-separator", "SEP", "Print paths using SEP as the separator"),
+    valued(None, "field", "N", "Match the query only against field N (counting from 1) of each line"),
+    valued(None, "delimiter", "CHAR", "Field delimiter for --field: a single ASCII character or \\t (default ,)"),
+    valued(None, "expr", "EXPR", "Match lines satisfying a boolean expression such as 'ERROR & !timeout' (operators: ! & | and parentheses)"),
+    valued(None, "pre", "COMMAND", "Search the output of `COMMAND FILE` instead of each file"),
+    valued(None, "pre-glob", "GLOB", "Only run the --pre command on files matching GLOB (may be repeated)"),
+    valued(None, "since", "TIME", "Only search lines whose leading timestamp is at or after TIME (lines must be in time order)"),
+    valued(None, "until", "TIME", "Only search lines whose leading timestamp is before TIME"),
+    valued(None, "time-format", "FORMAT", "Timestamp format for --since and --until using %Y %m %d %H %M %S %f (default %Y-%m-%dT%H:%M:%S)"),
+    flag(None, "code-only", "Ignore matches inside comments and string literals (Rust and C-like sources only; other files are skipped)"),
+    flag(None, "comments-only", "Only match inside comments (Rust and C-like sources only; other files are skipped)"),
+    flag(None, "no-messages", "Do not report files that cannot be read"),
+    flag(None, "no-config", "Ignore the configuration file"),
+    OptionSpec {
+        choices: &["completions", "man"],
+        ..valued(None, "generate", "KIND", "Print shell completions (completions bash|zsh|fish) or a man page")
+    },
+];
+
+// 找到参数对应的选项，返回选项和直接写在参数里的值：--name=value 或者 -m5
+pub fn lookup(arg: &str) -> Option<(&'static OptionSpec, Option<&str>)> {
+    if let Some(long) = arg.strip_prefix("--") {
+        let (name, inline) = match long.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (long, None),
+        };
+        return OPTIONS.iter().find(|option| option.long == name).map(|option| (option, inline));
+    }
+
+    let mut chars = arg.strip_prefix('-')?.chars();
+    let short = chars.next()?;
+    let rest = chars.as_str();
+    let option = OPTIONS.iter().find(|option| option.short == Some(short))?;
+    match (rest.is_empty(), option.value) {
+        (true, _) => Some((option, None)),
+        (false, Some(_)) => Some((option, Some(rest))),
+        // 不支持把多个短选项写在一起，比如 -in
+        (false, None) => None,
+    }
+}
+
+// 要生成的内容
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generate {
+    Completions(Shell),
+    Man,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Generate {
+    // completions 后面还需要一个参数指定 shell
+    pub fn parse(kind: &str, shell: Option<&str>) -> Result<Generate, MinigrepError> {
+        match (kind, shell) {
+            ("man", _) => Ok(Generate::Man),
+            ("completions", Some("bash")) => Ok(Generate::Completions(Shell::Bash)),
+            ("completions", Some("zsh")) => Ok(Generate::Completions(Shell::Zsh)),
+            ("completions", Some("fish")) => Ok(Generate::Completions(Shell::Fish)),
+            ("completions", _) => Err(MinigrepError::Argument(
+                "--generate completions expects one of bash, zsh, fish".to_string(),
+            )),
+            _ => Err(MinigrepError::Argument(format!(
+                "--generate expects completions or man, got {}",
+                kind
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn every_option_is_parsed() {
+        for option in OPTIONS {
+            let mut args = vec!["minigrep".to_string(), format!("--{}", option.long)];
+            if option.value.is_some() {
+                args.push(option.choices.first().unwrap_or(&"1").to_string());
+            }
+            match option.long {
+                "generate" => args[2] = "man".to_string(),
+                "since" | "until" => args[2] = "2024-05-01".to_string(),
+                "time-format" => args[2] = "%Y".to_string(),
+                "pre-glob" => args.extend(["--pre".to_string(), "cat".to_string()]),
+                _ => {}
+            }
+            args.extend(["to".to_string(), "poem.txt".to_string()]);
+            assert!(Config::build_from(&args, &[], None).is_ok(), "--{} is not handled", option.long);
+
+            let same_name = OPTIONS
+                .iter()
+                .filter(|other| other.long == option.long || (option.short.is_some() && other.short == option.short));
+            assert_eq!(1, same_name.count(), "--{} is defined twice", option.long);
+        }
+    }
+
+    #[test]
+    fn lookup_forms() {
+        assert_eq!(Some("5"), lookup("-m5").unwrap().1);
+        assert_eq!(Some("path"), lookup("--sort=path").unwrap().1);
+        assert_eq!("null", lookup("-0").unwrap().0.long);
+        assert!(lookup("-in").is_none());
+        assert!(lookup("--bogus").is_none());
+    }
+}
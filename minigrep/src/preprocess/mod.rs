@@ -0,0 +1,95 @@
+// 预处理器：搜索之前先把文件交给外部命令转换，比如把 PDF 转成文本，搜索的是命令的标准输出
+use std::fs::File;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::{Config, MinigrepError};
+
+// 这个文件需要交给哪个命令预处理，不需要时返回 None。没有 --pre-glob 时所有文件都需要
+pub fn command<'c>(config: &'c Config, path: &Path) -> Option<&'c str> {
+    let command = config.pre.as_deref()?;
+    (config.pre_globs.is_empty() || config.pre_globs.iter().any(|glob| glob_matches(glob, path))).then_some(command)
+}
+
+// 运行 `COMMAND PATH`，文件内容同时作为标准输入，命令的标准错误直接显示给用户
+pub fn run(command: &str, path: &Path) -> Result<Vec<u8>, MinigrepError> {
+    let input = File::open(path).map_err(MinigrepError::io(path))?;
+    let output = Command::new(command)
+        .arg(path)
+        .stdin(input)
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| MinigrepError::Preprocessor {
+            command: command.to_string(),
+            path: None,
+            reason: e.to_string(),
+        })?;
+
+    // 命令失败时它的输出可能是不完整的，不能当作文件的内容
+    if !output.status.success() {
+        return Err(MinigrepError::Preprocessor {
+            command: command.to_string(),
+            path: Some(path.display().to_string()),
+            reason: output.status.to_string(),
+        });
+    }
+    Ok(output.stdout)
+}
+
+// 不含 / 的模式只和文件名比较，比如 *.pdf；含 / 的模式和整个路径比较
+fn glob_matches(glob: &str, path: &Path) -> bool {
+    let target = if glob.contains('/') {
+        path.to_string_lossy()
+    } else {
+        path.file_name().unwrap_or(path.as_os_str()).to_string_lossy()
+    };
+    let glob: Vec<char> = glob.chars().collect();
+    let target: Vec<char> = target.chars().collect();
+    wildcard(&glob, &target)
+}
+
+// * 匹配任意多个字符，? 匹配一个字符。
+// 遇到 * 时记下位置，后面匹配失败就回到那里让 * 多吞一个字符，这样不需要递归
+fn wildcard(glob: &[char], text: &[char]) -> bool {
+    let (mut g, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match glob.get(g) {
+            Some('*') => {
+                star = Some((g, t));
+                g += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                g += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_g, star_t)) => {
+                    g = star_g + 1;
+                    t = star_t + 1;
+                    star = Some((star_g, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        assert!(glob_matches("*.pdf", Path::new("docs/manual.pdf")));
+        assert!(!glob_matches("*.pdf", Path::new("docs/manual.pdf.txt")));
+        assert!(glob_matches("docs/*.pdf", Path::new("docs/manual.pdf")));
+        assert!(!glob_matches("docs/*.pdf", Path::new("other/manual.pdf")));
+        assert!(glob_matches("a?c*", Path::new("abcdef")));
+        assert!(glob_matches("*a*b", Path::new("xxaxxab")));
+        assert!(!glob_matches("?", Path::new("")));
+        assert!(glob_matches("*", Path::new("")));
+    }
+}
@@ -1,10 +1,214 @@
+use crate::case_insensitive::fold_case;
+use crate::expr::Expr;
+use crate::fuzzy::FuzzyMatcher;
+
+// &str 版本只是 search_bytes 的包装，按 \n 切开的每一行仍然是合法的 UTF-8
 pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    search_bytes(query.as_bytes(), contents.as_bytes())
+        .into_iter()
+        .map(|line| std::str::from_utf8(line).expect("lines of a str are valid UTF-8"))
+        .collect()
+}
+
+// 按字节搜索，可以处理 Latin-1 之类不是 UTF-8 的内容
+pub fn search_bytes<'a>(query: &[u8], contents: &'a [u8]) -> Vec<&'a [u8]> {
     let mut results = Vec::new();
 
-    for line in contents.lines() {
-        if line.contains(query) {
+    for line in byte_lines(contents) {
+        if contains_bytes(line, query, false) {
             results.push(line);
         }
     }
     results
-}
\ No newline at end of file
+}
+
+// 和 str::lines 一样按 \n 或 \r\n 切分，但是不要求内容是 UTF-8
+pub fn byte_lines(contents: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = contents;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        match rest.iter().position(|&b| b == b'\n') {
+            Some(end) => {
+                let line = &rest[..end];
+                rest = &rest[end + 1..];
+                Some(line.strip_suffix(b"\r").unwrap_or(line))
+            }
+            None => Some(std::mem::take(&mut rest)),
+        }
+    })
+}
+
+// 忽略大小写时只能处理 ASCII 字母，其它字节的大小写和编码有关
+pub fn contains_bytes(haystack: &[u8], needle: &[u8], ignore_case: bool) -> bool {
+    if needle.is_empty() {
+        return true;
+    }
+    haystack.windows(needle.len()).any(|window| {
+        if ignore_case {
+            window.eq_ignore_ascii_case(needle)
+        } else {
+            window == needle
+        }
+    })
+}
+
+// 判断一行是否匹配查询，普通模式和逐行读取文件时共用
+pub struct LineMatcher {
+    query: String,
+    ignore_case: bool,
+}
+
+impl LineMatcher {
+    pub fn new(query: &str, ignore_case: bool) -> LineMatcher {
+        let query = if ignore_case {
+            fold_case(query)
+        } else {
+            query.to_string()
+        };
+        LineMatcher { query, ignore_case }
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        if self.ignore_case {
+            fold_case(line).contains(&self.query)
+        } else {
+            line.contains(&self.query)
+        }
+    }
+
+    // 第一次出现的字节范围。忽略大小写时先在转换后的行中查找，再换算回原来的行，
+    // 因为有些字符转换成小写之后长度会变（比如 İ）
+    pub fn find(&self, line: &str) -> Option<(usize, usize)> {
+        if !self.ignore_case || self.query.is_empty() {
+            return line.find(&self.query).map(|start| (start, start + self.query.len()));
+        }
+
+        let folded = fold_case(line);
+        let folded_start = folded.find(&self.query)?;
+        let folded_end = folded_start + self.query.len();
+        let (mut start, mut folded_pos) = (None, 0);
+        for (i, c) in line.char_indices() {
+            let next = folded_pos + c.to_lowercase().map(char::len_utf8).sum::<usize>();
+            if start.is_none() && next > folded_start {
+                start = Some(i);
+            }
+            if next >= folded_end {
+                return Some((start.unwrap_or(i), i + c.len_utf8()));
+            }
+            folded_pos = next;
+        }
+        None
+    }
+
+    // 合法的 UTF-8 按字符串匹配，这样忽略大小写时能正确处理 Unicode 字符
+    pub fn is_match_bytes(&self, line: &[u8]) -> bool {
+        match std::str::from_utf8(line) {
+            Ok(line) => self.is_match(line),
+            Err(_) => contains_bytes(line, self.query.as_bytes(), self.ignore_case),
+        }
+    }
+}
+
+// 一行匹配的结果。span 是最佳匹配在输出的这一行（不是 UTF-8 的字节已经替换掉）中的字节范围，
+// 布尔表达式没有单一的匹配位置，所以是 None
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineMatch {
+    pub span: Option<(usize, usize)>,
+}
+
+// 逐行搜索时使用的匹配方式
+pub enum Matcher {
+    Literal(LineMatcher),
+    Fuzzy(FuzzyMatcher),
+    // --expr：每个词是一个匹配器，用布尔运算组合起来
+    Expr(Box<Expr<Matcher>>),
+}
+
+impl Matcher {
+    pub fn is_match(&self, line: &str) -> bool {
+        match self {
+            Matcher::Literal(matcher) => matcher.is_match(line),
+            Matcher::Fuzzy(matcher) => matcher.is_match(line),
+            Matcher::Expr(expr) => expr.eval(&|matcher: &Matcher| matcher.is_match(line)),
+        }
+    }
+
+    pub fn is_match_bytes(&self, line: &[u8]) -> bool {
+        self.find_bytes(line).is_some()
+    }
+
+    // 判断是否匹配，同时给出匹配的位置，调用者不需要为了位置再匹配一遍。
+    // 在替换掉无效字节的行上匹配，位置才和输出的内容一致。查询本身是 UTF-8，不会匹配到无效的字节，
+    // 所以结果和按字节匹配一样
+    pub fn find_bytes(&self, line: &[u8]) -> Option<LineMatch> {
+        match self {
+            Matcher::Literal(matcher) => matcher
+                .find(&String::from_utf8_lossy(line))
+                .map(|span| LineMatch { span: Some(span) }),
+            Matcher::Fuzzy(matcher) => matcher
+                .find(&String::from_utf8_lossy(line))
+                .map(|found| LineMatch { span: Some((found.start, found.end)) }),
+            Matcher::Expr(expr) => expr
+                .eval(&|matcher: &Matcher| matcher.is_match_bytes(line))
+                .then_some(LineMatch { span: None }),
+        }
+    }
+}
+
+// 和 search 一样，但同时返回从 1 开始的行号
+// 返回的是惰性的迭代器，调用者只取前几个结果时不会搜索剩下的内容
+pub fn search_numbered<'a>(
+    query: &str,
+    contents: &'a str,
+    ignore_case: bool,
+) -> impl Iterator<Item = (usize, &'a str)> {
+    search_bytes_numbered(query, contents.as_bytes(), ignore_case)
+        .map(|(number, line)| (number, std::str::from_utf8(line).expect("lines of a str are valid UTF-8")))
+}
+
+pub fn search_bytes_numbered<'a>(
+    query: &str,
+    contents: &'a [u8],
+    ignore_case: bool,
+) -> impl Iterator<Item = (usize, &'a [u8])> {
+    let matcher = LineMatcher::new(query, ignore_case);
+
+    byte_lines(contents)
+        .enumerate()
+        .filter(move |(_, line)| matcher.is_match_bytes(line))
+        .map(|(i, line)| (i + 1, line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latin1_bytes() {
+        // "café au lait\nCAFÉ" 的 Latin-1 编码，é 是单个字节 0xe9
+        let contents = b"caf\xe9 au lait\r\nCAF\xc9\nthe end";
+
+        assert_eq!(vec![&b"caf\xe9 au lait"[..]], search_bytes(b"caf\xe9", contents));
+        assert_eq!(
+            vec![(1, &b"caf\xe9 au lait"[..]), (2, &b"CAF\xc9"[..])],
+            search_bytes_numbered("CAF", contents, true).collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["the end"], search("end", "caf\u{e9}\nthe end"));
+    }
+
+    #[test]
+    fn literal_spans() {
+        assert_eq!(Some((4, 7)), LineMatcher::new("end", false).find("the end"));
+        assert_eq!(None, LineMatcher::new("End", false).find("the end"));
+        // İ 转换成小写是两个字符，位置要按原来的行计算
+        assert_eq!(Some((2, 4)), LineMatcher::new("i", true).find("ab\u{130}c"));
+        assert_eq!(Some((3, 5)), LineMatcher::new("cd", true).find("\u{130}xCD"));
+
+        let matcher = Matcher::Literal(LineMatcher::new("to", false));
+        assert_eq!(Some(LineMatch { span: Some((4, 6)) }), matcher.find_bytes(b"not to"));
+        // 位置是替换成 U+FFFD 之后的
+        assert_eq!(Some(LineMatch { span: Some((4, 6)) }), matcher.find_bytes(b"\xff to"));
+    }
+}
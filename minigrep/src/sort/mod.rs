@@ -0,0 +1,126 @@
+// 结果的排序和去重：--sort/--sortr 决定搜索文件的顺序，--unique 让相同的行只输出一次
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::MinigrepError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Path,
+    Modified,
+    Created,
+}
+
+impl SortKey {
+    pub fn parse(value: &str) -> Result<SortKey, MinigrepError> {
+        match value {
+            "path" => Ok(SortKey::Path),
+            "modified" => Ok(SortKey::Modified),
+            "created" => Ok(SortKey::Created),
+            _ => Err(MinigrepError::Argument(format!(
+                "--sort expects one of path, modified, created, got {}",
+                value
+            ))),
+        }
+    }
+}
+
+// 取不到时间的文件（比如文件系统不支持创建时间）排在最前面
+fn file_time(path: &Path, key: SortKey) -> Option<SystemTime> {
+    let metadata = fs::metadata(path).ok()?;
+    match key {
+        SortKey::Modified => metadata.modified().ok(),
+        SortKey::Created => metadata.created().ok(),
+        SortKey::Path => None,
+    }
+}
+
+pub fn sort_files(files: &mut [PathBuf], key: SortKey, reverse: bool) {
+    // 按路径的字节排序，而不是 PathBuf 按组成部分比较的顺序，和路径是字符串时的结果一样
+    match key {
+        SortKey::Path => files.sort_by(|a, b| a.as_os_str().cmp(b.as_os_str())),
+        // 每个文件只读取一次元数据，时间相同时按路径排序，保证结果稳定
+        _ => files.sort_by_cached_key(|path| (file_time(path, key), path.as_os_str().to_owned())),
+    }
+    if reverse {
+        files.reverse();
+    }
+}
+
+// 按第一次出现的顺序记录不同的行和出现的次数。
+// 实现了 Write，搜索时把它当作输出，每写入一行就记录一次
+#[derive(Debug, Default)]
+pub struct Unique {
+    order: Vec<String>,
+    counts: HashMap<String, usize>,
+    // 还没有写完的一行
+    partial: Vec<u8>,
+}
+
+impl Unique {
+    pub fn add(&mut self, line: &str) {
+        match self.counts.get_mut(line) {
+            Some(count) => *count += 1,
+            None => {
+                self.counts.insert(line.to_string(), 1);
+                self.order.push(line.to_string());
+            }
+        }
+    }
+
+    // with_count 时和 `uniq -c` 一样在每行前面加上出现次数
+    pub fn print(&self, with_count: bool, out: &mut impl Write) -> io::Result<()> {
+        for line in &self.order {
+            if with_count {
+                writeln!(out, "{:>7} {}", self.counts[line], line)?;
+            } else {
+                writeln!(out, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for Unique {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.partial.extend_from_slice(buf);
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            self.add(&String::from_utf8_lossy(&line[..end]));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_keeps_first_order() {
+        let mut unique = Unique::default();
+        unique.add("to be");
+        write!(unique, "not to\nto ").unwrap();
+        writeln!(unique, "be").unwrap();
+        unique.add("to be");
+
+        let mut out = Vec::new();
+        unique.print(true, &mut out).unwrap();
+        assert_eq!("      3 to be\n      1 not to\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn sort_by_path() {
+        let mut files: Vec<PathBuf> = ["b.txt", "a/z.txt", "a.txt"].iter().map(PathBuf::from).collect();
+        sort_files(&mut files, SortKey::Path, true);
+        assert_eq!(vec![Path::new("b.txt"), Path::new("a/z.txt"), Path::new("a.txt")], files);
+        assert!(SortKey::parse("size").is_err());
+    }
+}
@@ -0,0 +1,64 @@
+// --stats 的统计信息，搜索结束后输出到标准错误，不会混进搜索结果里
+use std::io::{self, Write};
+use std::time::Duration;
+
+// 一个文件的搜索结果
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FileStats {
+    pub lines_matched: usize,
+    // 实际读取的字节数，提前结束时不包括没有读到的部分
+    pub bytes_searched: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub files_searched: usize,
+    pub files_matched: usize,
+    pub lines_matched: usize,
+    pub bytes_searched: u64,
+    // 各个阶段的耗时：收集要搜索的文件（遍历目录、使用索引）和搜索文件内容
+    pub collect_time: Duration,
+    pub search_time: Duration,
+}
+
+impl Stats {
+    pub fn add(&mut self, file: FileStats) {
+        self.files_searched += 1;
+        if file.lines_matched > 0 {
+            self.files_matched += 1;
+        }
+        self.lines_matched += file.lines_matched;
+        self.bytes_searched += file.bytes_searched;
+    }
+
+    pub fn print(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "files searched: {}", self.files_searched)?;
+        writeln!(out, "files matched: {}", self.files_matched)?;
+        writeln!(out, "lines matched: {}", self.lines_matched)?;
+        writeln!(out, "bytes searched: {}", self.bytes_searched)?;
+        writeln!(out, "collect time: {:.6}s", self.collect_time.as_secs_f64())?;
+        writeln!(out, "search time: {:.6}s", self.search_time.as_secs_f64())?;
+        writeln!(
+            out,
+            "total time: {:.6}s",
+            (self.collect_time + self.search_time).as_secs_f64()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulates_files() {
+        let mut stats = Stats::default();
+        stats.add(FileStats { lines_matched: 2, bytes_searched: 100 });
+        stats.add(FileStats { lines_matched: 0, bytes_searched: 50 });
+
+        let mut out = Vec::new();
+        stats.print(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("files searched: 2\nfiles matched: 1\nlines matched: 2\nbytes searched: 150\n"));
+    }
+}
@@ -0,0 +1,60 @@
+// 测试共用的辅助函数
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use crate::{collect_files, has_many_files, search_file, Config};
+
+// 按命令行参数（不含程序名）搜索，返回输出的内容。和 run 一样决定是否输出文件名，目录会被展开
+pub fn output(flags: &[&str]) -> String {
+    let mut args = vec!["minigrep".to_string()];
+    args.extend(flags.iter().map(|flag| flag.to_string()));
+    let mut config = Config::build_from(&args, &[], None).unwrap();
+    config.with_filename = has_many_files(&config.file_paths);
+    let mut out = Vec::new();
+    for path in collect_files(&config.file_paths).unwrap() {
+        search_file(&config, &path, &mut out).unwrap();
+    }
+    String::from_utf8(out).unwrap()
+}
+
+// 临时文件或目录，离开作用域时删除，断言失败时也不会留下。
+// 名字中带上进程号，同时运行的测试不会互相干扰；扩展名保留，按扩展名处理的功能也能测试
+pub struct Temp(PathBuf);
+
+impl Temp {
+    pub fn file(name: &str, contents: impl AsRef<[u8]>) -> Temp {
+        let temp = Temp::named(name);
+        fs::write(&temp.0, contents).unwrap();
+        temp
+    }
+
+    pub fn dir(name: &str) -> Temp {
+        let temp = Temp::named(name);
+        fs::create_dir_all(&temp.0).unwrap();
+        temp
+    }
+
+    fn named(name: &str) -> Temp {
+        Temp(std::env::temp_dir().join(format!("minigrep-{}-{}", std::process::id(), name)))
+    }
+
+    pub fn path(&self) -> String {
+        self.0.to_string_lossy().into_owned()
+    }
+}
+
+// 可以直接当作路径使用，比如 dir.join("a.txt")
+impl Deref for Temp {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Temp {
+    fn drop(&mut self) {
+        let _ = if self.0.is_dir() { fs::remove_dir_all(&self.0) } else { fs::remove_file(&self.0) };
+    }
+}
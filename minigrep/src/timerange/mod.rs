@@ -0,0 +1,330 @@
+// 按时间范围过滤日志：每行开头是时间戳，只在 --since 和 --until 之间的行中搜索。
+// 假设日志按时间排好序，所以可以二分查找起始位置，遇到晚于 --until 的行就停止
+use std::io::{self, BufRead, Seek, SeekFrom};
+
+use crate::MinigrepError;
+
+pub const DEFAULT_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Item {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    // 秒的小数部分，1 到 9 位
+    Fraction,
+    Literal(u8),
+}
+
+// (年, 月, 日, 时, 分, 秒, 纳秒)，按字段顺序比较就是按时间先后比较，不考虑时区
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp([u32; 7]);
+
+#[derive(Debug, Clone)]
+pub struct TimeRange {
+    format: Vec<Item>,
+    // 包含 since，不包含 until
+    since: Option<Timestamp>,
+    until: Option<Timestamp>,
+}
+
+// strftime 风格的格式，只支持 %Y %m %d %H %M %S %f 和 %%
+fn parse_format(format: &str) -> Result<Vec<Item>, MinigrepError> {
+    let mut items = Vec::new();
+    let mut bytes = format.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'%' {
+            items.push(Item::Literal(b));
+            continue;
+        }
+        items.push(match bytes.next() {
+            Some(b'Y') => Item::Year,
+            Some(b'm') => Item::Month,
+            Some(b'd') => Item::Day,
+            Some(b'H') => Item::Hour,
+            Some(b'M') => Item::Minute,
+            Some(b'S') => Item::Second,
+            Some(b'f') => Item::Fraction,
+            Some(b'%') => Item::Literal(b'%'),
+            _ => {
+                return Err(MinigrepError::Argument(format!(
+                    "--time-format supports %Y %m %d %H %M %S %f and %%, got {}",
+                    format
+                )))
+            }
+        });
+    }
+    if !items.iter().any(|item| !matches!(item, Item::Literal(_))) {
+        return Err(MinigrepError::Argument(format!("--time-format has no time fields: {}", format)));
+    }
+    Ok(items)
+}
+
+// 从 text 开头解析时间戳，返回时间戳和用掉的字节数。
+// partial 为 true 时允许在某个字段之后提前结束，比如 2024-05-01 表示这一天的 0 点
+fn parse_timestamp(format: &[Item], text: &[u8], partial: bool) -> Option<(Timestamp, usize)> {
+    // 没有写的月和日是 1，其余是 0
+    let mut fields = [0, 1, 1, 0, 0, 0, 0];
+    let mut pos = 0;
+
+    for (i, item) in format.iter().enumerate() {
+        if partial && pos == text.len() && i > 0 && !matches!(format[i - 1], Item::Literal(_)) {
+            break;
+        }
+        let (slot, width) = match *item {
+            Item::Literal(b) => {
+                if text.get(pos) != Some(&b) {
+                    return None;
+                }
+                pos += 1;
+                continue;
+            }
+            Item::Year => (0, 4),
+            Item::Month => (1, 2),
+            Item::Day => (2, 2),
+            Item::Hour => (3, 2),
+            Item::Minute => (4, 2),
+            Item::Second => (5, 2),
+            Item::Fraction => (6, 9),
+        };
+
+        let digits = text[pos..].iter().take(width).take_while(|b| b.is_ascii_digit()).count();
+        // 小数部分的位数不固定，其它字段必须写满位数
+        if digits == 0 || (digits < width && *item != Item::Fraction) {
+            return None;
+        }
+        let mut value = text[pos..pos + digits].iter().fold(0, |n, b| n * 10 + (b - b'0') as u32);
+        if *item == Item::Fraction {
+            value *= 10u32.pow((width - digits) as u32);
+        }
+        // 月份 13、小时 99 这样不可能的值不是时间戳。秒可以是 60（闰秒）
+        let valid = match item {
+            Item::Month => (1..=12).contains(&value),
+            Item::Day => (1..=31).contains(&value),
+            Item::Hour => value <= 23,
+            Item::Minute => value <= 59,
+            Item::Second => value <= 60,
+            _ => true,
+        };
+        if !valid {
+            return None;
+        }
+        fields[slot] = value;
+        pos += digits;
+    }
+
+    Some((Timestamp(fields), pos))
+}
+
+impl TimeRange {
+    pub fn new(format: Option<&str>, since: Option<&str>, until: Option<&str>) -> Result<TimeRange, MinigrepError> {
+        let format_text = format.unwrap_or(DEFAULT_FORMAT);
+        let format = parse_format(format_text)?;
+        let bound = |flag: &str, value: Option<&str>| {
+            value
+                .map(|value| match parse_timestamp(&format, value.as_bytes(), true) {
+                    Some((timestamp, used)) if used == value.len() => Ok(timestamp),
+                    _ => Err(MinigrepError::Argument(format!(
+                        "{} expects a time in the format {}, got {}",
+                        flag, format_text, value
+                    ))),
+                })
+                .transpose()
+        };
+        let since = bound("--since", since)?;
+        let until = bound("--until", until)?;
+        Ok(TimeRange { format, since, until })
+    }
+
+    // 行首的时间戳，没有时间戳的行（比如异常的调用栈）返回 None
+    fn timestamp(&self, line: &[u8]) -> Option<Timestamp> {
+        parse_timestamp(&self.format, line, false).map(|(timestamp, _)| timestamp)
+    }
+
+    // 从 pos 开始（包括 pos）第一个有时间戳的行，返回 (行首位置, 时间戳)
+    fn next_stamped(&self, reader: &mut (impl BufRead + Seek), pos: u64) -> io::Result<Option<(u64, Timestamp)>> {
+        let mut line = Vec::new();
+        // pos 不一定在行首：从前一个字节开始跳过一行，如果前一个字节就是换行符，只会跳过它自己
+        let mut start = pos;
+        if pos > 0 {
+            reader.seek(SeekFrom::Start(pos - 1))?;
+            start = pos - 1 + reader.read_until(b'\n', &mut line)? as u64;
+        } else {
+            reader.seek(SeekFrom::Start(0))?;
+        }
+
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                return Ok(None);
+            }
+            if let Some(timestamp) = self.timestamp(&line) {
+                return Ok(Some((start, timestamp)));
+            }
+            start += read as u64;
+        }
+    }
+
+    // 二分查找第一个不早于 --since 的行，把 reader 移到那里。
+    // 返回跳过的行数，只有 count_lines 为 true 时才数，因为这需要把跳过的部分读一遍
+    pub fn seek_since(&self, reader: &mut (impl BufRead + Seek), count_lines: bool) -> io::Result<usize> {
+        let since = match self.since {
+            Some(since) => since,
+            None => return Ok(0),
+        };
+
+        let (mut low, mut high) = (0, reader.seek(SeekFrom::End(0))?);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.next_stamped(reader, mid)? {
+                Some((_, timestamp)) if timestamp < since => low = mid + 1,
+                _ => high = mid,
+            }
+        }
+        let start = match self.next_stamped(reader, low)? {
+            Some((start, _)) => start,
+            None => reader.seek(SeekFrom::End(0))?,
+        };
+
+        let mut skipped = 0;
+        if count_lines {
+            reader.seek(SeekFrom::Start(0))?;
+            let mut line = Vec::new();
+            let mut pos = 0;
+            while pos < start {
+                line.clear();
+                pos += reader.read_until(b'\n', &mut line)? as u64;
+                skipped += 1;
+            }
+        }
+        reader.seek(SeekFrom::Start(start))?;
+        Ok(skipped)
+    }
+}
+
+// 一行在不在范围内，Stop 表示这一行和之后的行都晚于 --until
+enum Verdict {
+    Keep,
+    Skip,
+    Stop,
+}
+
+// 没有时间戳的行属于前面最近的有时间戳的行，文件开头没有时间戳的行不在任何范围内
+struct Filter<'r> {
+    range: &'r TimeRange,
+    current: Option<Timestamp>,
+}
+
+impl Filter<'_> {
+    fn check(&mut self, line: &[u8]) -> Verdict {
+        if let Some(timestamp) = self.range.timestamp(line) {
+            self.current = Some(timestamp);
+        }
+        match self.current {
+            None => Verdict::Skip,
+            Some(t) if self.range.until.is_some_and(|until| t >= until) => Verdict::Stop,
+            Some(t) if self.range.since.is_some_and(|since| t < since) => Verdict::Skip,
+            Some(_) => Verdict::Keep,
+        }
+    }
+}
+
+// 只保留时间范围内的行，range 为 None 时原样返回。line 取出每一项的内容，返回 None 的项（比如读取错误）原样保留
+pub fn filter<'r, T: 'r>(
+    range: Option<&'r TimeRange>,
+    items: impl Iterator<Item = T> + 'r,
+    line: impl Fn(&T) -> Option<&[u8]> + 'r,
+) -> impl Iterator<Item = T> + 'r {
+    let mut filter = range.map(|range| Filter { range, current: None });
+    items
+        .map_while(move |item| {
+            let verdict = match (&mut filter, line(&item)) {
+                (Some(filter), Some(bytes)) => filter.check(bytes),
+                _ => Verdict::Keep,
+            };
+            match verdict {
+                Verdict::Keep => Some(Some(item)),
+                Verdict::Skip => Some(None),
+                Verdict::Stop => None,
+            }
+        })
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Cursor};
+
+    const LOG: &str = "\
+2024-05-01T09:59:58 start
+2024-05-01T10:00:00.250 request one
+  at handler
+2024-05-01T10:00:00.750 request two
+2024-05-01T10:30:00 idle
+2024-05-02T00:00:00 rollover
+";
+
+    fn kept(range: &TimeRange, text: &str) -> Vec<String> {
+        filter(Some(range), text.lines(), |line| Some(line.as_bytes()))
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn range_bounds_and_continuation_lines() {
+        let range = TimeRange::new(None, Some("2024-05-01T10:00"), Some("2024-05-01T10:30")).unwrap();
+        assert_eq!(
+            vec!["2024-05-01T10:00:00.250 request one", "  at handler", "2024-05-01T10:00:00.750 request two"],
+            kept(&range, LOG)
+        );
+
+        let range = TimeRange::new(Some("[%d/%m/%Y %H:%M:%S.%f]"), Some("[01/05/2024 10:00:00.5"), None).unwrap();
+        assert_eq!(
+            vec!["[01/05/2024 10:00:00.75] b"],
+            kept(&range, "[01/05/2024 10:00:00.25] a\n[01/05/2024 10:00:00.75] b\n")
+        );
+
+        assert!(TimeRange::new(Some("%Q"), None, None).is_err());
+        assert!(TimeRange::new(None, Some("2024-5-1"), None).is_err());
+        assert!(TimeRange::new(None, Some("2024-05-01T"), None).is_err());
+        for impossible in ["2024-13-01", "2024-00-01", "2024-05-32", "2024-05-01T99", "2024-05-01T10:60"] {
+            match TimeRange::new(None, Some(impossible), None) {
+                Err(MinigrepError::Argument(message)) => assert_eq!(
+                    format!("--since expects a time in the format {}, got {}", DEFAULT_FORMAT, impossible),
+                    message
+                ),
+                other => panic!("{} was accepted: {:?}", impossible, other),
+            }
+        }
+        // 不可能的时间戳开头的行当作没有时间戳，属于前面的行
+        let range = TimeRange::new(None, Some("2024-05-01T10"), None).unwrap();
+        assert_eq!(
+            vec!["2024-05-01T10:00:00 a", "2024-05-01T99:00:00 b"],
+            kept(&range, "2024-05-01T10:00:00 a\n2024-05-01T99:00:00 b\n")
+        );
+    }
+
+    #[test]
+    fn binary_search_finds_first_line() {
+        let seek = |since: &str, count_lines: bool| {
+            let range = TimeRange::new(None, Some(since), None).unwrap();
+            let mut reader = BufReader::new(Cursor::new(LOG.as_bytes()));
+            let skipped = range.seek_since(&mut reader, count_lines).unwrap();
+            let mut rest = String::new();
+            reader.read_line(&mut rest).unwrap();
+            (skipped, rest)
+        };
+
+        assert_eq!((0, "2024-05-01T09:59:58 start\n".to_string()), seek("2024", true));
+        assert_eq!((1, "2024-05-01T10:00:00.250 request one\n".to_string()), seek("2024-05-01T10", true));
+        // 调用栈属于前一个请求，从下一个有时间戳的行开始
+        assert_eq!((4, "2024-05-01T10:30:00 idle\n".to_string()), seek("2024-05-01T10:00:01", true));
+        assert_eq!((0, "2024-05-02T00:00:00 rollover\n".to_string()), seek("2024-05-01T11", false));
+        assert_eq!((6, String::new()), seek("2025", true));
+    }
+}
@@ -0,0 +1,377 @@
+// 交互模式：在终端中滚动浏览搜索结果，边输入边更新查询，并预览选中结果附近的内容
+use std::io;
+
+use crate::{
+    display_chain, line_matcher, line_matches, outcome, read_contents, walk_paths, warn, Config, MinigrepError, Outcome,
+};
+
+mod terminal;
+use terminal::AnsiTerminal;
+
+// 预览区中选中行上下各显示几行
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Backspace,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Enter,
+    Esc,
+}
+
+// 终端后端，真实终端和测试中的假终端都实现这个 trait
+pub trait Backend {
+    // 返回 (宽, 高)
+    fn size(&mut self) -> io::Result<(usize, usize)>;
+    // 绘制一整屏内容，每个元素是一行
+    fn draw(&mut self, frame: &[String]) -> io::Result<()>;
+    // 没有更多输入时返回 None
+    fn read_key(&mut self) -> io::Result<Option<Key>>;
+}
+
+// 一条搜索结果：文件下标和从 0 开始的行下标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Hit {
+    file: usize,
+    line: usize,
+}
+
+pub struct App<'c> {
+    config: &'c Config,
+    files: Vec<(String, Vec<String>)>,
+    query: String,
+    hits: Vec<Hit>,
+    selected: usize,
+    // 结果列表中第一行显示的是第几条结果
+    scroll: usize,
+    // 用户按回车确认了选中的结果
+    accepted: bool,
+    // 当前的查询不合法（比如模糊查询太长）时显示在状态行，会话继续，改好查询后恢复
+    error: Option<String>,
+}
+
+impl<'c> App<'c> {
+    pub fn new(config: &'c Config, files: Vec<(String, String)>) -> Result<App<'c>, MinigrepError> {
+        let files = files
+            .into_iter()
+            .map(|(path, contents)| (path, contents.lines().map(|line| line.to_string()).collect()))
+            .collect();
+
+        let mut app = App {
+            config,
+            files,
+            query: config.query.clone(),
+            hits: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            accepted: false,
+            error: None,
+        };
+        app.hits = app.search_all()?;
+        Ok(app)
+    }
+
+    fn search_all(&self) -> Result<Vec<Hit>, MinigrepError> {
+        let all = self.files.iter().enumerate().flat_map(|(file, (_, lines))| {
+            (0..lines.len()).map(move |line| Hit { file, line })
+        });
+        self.filter(all)
+    }
+
+    // 只在给定的候选结果中搜索
+    fn filter(&self, candidates: impl Iterator<Item = Hit>) -> Result<Vec<Hit>, MinigrepError> {
+        let ignore_case = self.config.case_mode.ignore_case(&self.query);
+        let matcher = line_matcher(self.config, &self.query, ignore_case)?;
+
+        Ok(candidates
+            .filter(|hit| line_matches(self.config, &matcher, self.files[hit.file].1[hit.line].as_bytes()))
+            .collect())
+    }
+
+    // 查询出错时没有结果，错误信息显示在状态行
+    fn update(&mut self, hits: Result<Vec<Hit>, MinigrepError>) {
+        match hits {
+            Ok(hits) => {
+                self.hits = hits;
+                self.error = None;
+            }
+            Err(e) => {
+                self.hits.clear();
+                self.error = Some(display_chain(&e));
+            }
+        }
+        self.selected = 0;
+    }
+
+    // 处理一个按键，返回 false 表示退出
+    fn handle_key(&mut self, key: Key, page: usize) -> bool {
+        match key {
+            Key::Char(c) => {
+                // 查询只是在末尾追加了字符时，新的结果一定是旧结果的子集，
+                // 所以只需要在旧结果中继续过滤，不用重新搜索所有文件。
+                // 之前出错时旧结果是空的，这时要重新搜索
+                self.query.push(c);
+                let hits = if self.error.is_some() {
+                    self.search_all()
+                } else {
+                    let previous = std::mem::take(&mut self.hits);
+                    self.filter(previous.into_iter())
+                };
+                self.update(hits);
+            }
+            Key::Backspace => {
+                if self.query.pop().is_some() {
+                    let hits = self.search_all();
+                    self.update(hits);
+                }
+            }
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected += 1,
+            Key::PageUp => self.selected = self.selected.saturating_sub(page),
+            Key::PageDown => self.selected += page,
+            Key::Enter => {
+                self.accepted = true;
+                return false;
+            }
+            Key::Esc => return false,
+        }
+
+        self.selected = self.selected.min(self.hits.len().saturating_sub(1));
+        true
+    }
+
+    fn selected_hit(&self) -> Option<Hit> {
+        self.hits.get(self.selected).copied()
+    }
+
+    // 选中的结果，格式和普通模式下搜索多个文件时一样
+    pub fn selection(&self) -> Option<String> {
+        self.selected_hit().map(|hit| {
+            let (path, lines) = &self.files[hit.file];
+            format!("{}:{}:{}", path, hit.line + 1, lines[hit.line])
+        })
+    }
+
+    // 上半部分是结果列表，下半部分是预览
+    fn list_height(height: usize) -> usize {
+        height.saturating_sub(2).div_ceil(2)
+    }
+
+    pub fn render(&mut self, width: usize, height: usize) -> Vec<String> {
+        let list_height = App::list_height(height);
+
+        // 让选中的结果始终在可见范围内
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if list_height > 0 && self.selected >= self.scroll + list_height {
+            self.scroll = self.selected + 1 - list_height;
+        }
+
+        let mut frame = Vec::with_capacity(height);
+        match &self.error {
+            Some(error) => frame.push(format!("> {}  ({})", self.query, error)),
+            None => frame.push(format!("> {}  ({} matches)", self.query, self.hits.len())),
+        }
+
+        for row in 0..list_height {
+            let index = self.scroll + row;
+            let text = match self.hits.get(index) {
+                Some(hit) => {
+                    let (path, lines) = &self.files[hit.file];
+                    let marker = if index == self.selected { '>' } else { ' ' };
+                    format!("{} {}:{}:{}", marker, path, hit.line + 1, lines[hit.line])
+                }
+                None => String::new(),
+            };
+            frame.push(text);
+        }
+
+        frame.push("-".repeat(width));
+
+        // 预览区放不下时，让选中的行尽量居中
+        let preview_height = height.saturating_sub(list_height + 2);
+        if let Some(hit) = self.selected_hit() {
+            let lines = &self.files[hit.file].1;
+            let start = hit.line.saturating_sub(CONTEXT.min(preview_height / 2));
+            let end = (start + preview_height).min(hit.line + CONTEXT + 1).min(lines.len());
+            for (i, line) in lines.iter().enumerate().take(end).skip(start) {
+                let marker = if i == hit.line { '>' } else { ' ' };
+                frame.push(format!("{}{:>5}: {}", marker, i + 1, line));
+            }
+        }
+
+        frame.resize(height, String::new());
+        frame
+            .into_iter()
+            .map(|line| line.chars().take(width).collect())
+            .collect()
+    }
+}
+
+// 事件循环：绘制、读取按键、更新状态，直到用户退出或者没有更多输入
+pub fn run_app<B: Backend>(backend: &mut B, app: &mut App) -> Result<(), MinigrepError> {
+    loop {
+        let (width, height) = backend.size()?;
+        backend.draw(&app.render(width, height))?;
+
+        let key = match backend.read_key()? {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        if !app.handle_key(key, App::list_height(height).max(1)) {
+            return Ok(());
+        }
+    }
+}
+
+// 启动交互模式，按回车退出时把选中的结果打印到标准输出
+pub fn run(config: &Config) -> Result<Outcome, MinigrepError> {
+    // 和普通搜索一样，读不了的目录和文件在进入交互界面之前报告并跳过
+    let mut errors = Vec::new();
+    let mut files = Vec::new();
+    for path in walk_paths(&config.file_paths, &mut errors) {
+        match read_contents(config, &path) {
+            // 交互模式中的路径只用来在终端中显示，不是 UTF-8 的字节会被替换掉
+            Ok(contents) => files.push((path.display().to_string(), contents)),
+            Err(error) if error.path().is_some() => errors.push(error),
+            Err(error) => return Err(error),
+        }
+    }
+    let mut messages = io::stderr();
+    for error in &errors {
+        warn(config, error, &mut messages);
+    }
+
+    let mut app = App::new(config, files)?;
+    {
+        // 离开这个作用域时终端会被恢复，之后的输出才能正常显示
+        let mut terminal = AnsiTerminal::open().map_err(MinigrepError::io("/dev/tty"))?;
+        run_app(&mut terminal, &mut app)?;
+    }
+
+    let matched = match app.selection() {
+        Some(selection) if app.accepted => {
+            println!("{}", selection);
+            true
+        }
+        _ => false,
+    };
+    Ok(outcome(config, matched, !errors.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    // 按照脚本依次返回按键，并记录每一次绘制的内容
+    struct FakeBackend {
+        width: usize,
+        height: usize,
+        keys: VecDeque<Key>,
+        frames: Vec<Vec<String>>,
+    }
+
+    impl FakeBackend {
+        fn new(width: usize, height: usize, keys: &str) -> FakeBackend {
+            let keys = keys
+                .chars()
+                .map(|c| match c {
+                    '↑' => Key::Up,
+                    '↓' => Key::Down,
+                    '⌫' => Key::Backspace,
+                    '⏎' => Key::Enter,
+                    c => Key::Char(c),
+                })
+                .collect();
+            FakeBackend { width, height, keys, frames: Vec::new() }
+        }
+    }
+
+    impl Backend for FakeBackend {
+        fn size(&mut self) -> io::Result<(usize, usize)> {
+            Ok((self.width, self.height))
+        }
+
+        fn draw(&mut self, frame: &[String]) -> io::Result<()> {
+            self.frames.push(frame.to_vec());
+            Ok(())
+        }
+
+        fn read_key(&mut self) -> io::Result<Option<Key>> {
+            Ok(self.keys.pop_front())
+        }
+    }
+
+    fn poem_app(config: &Config) -> App<'_> {
+        let poem = include_str!("../../poem.txt").to_string();
+        App::new(config, vec![("poem.txt".to_string(), poem)]).unwrap()
+    }
+
+    fn config(query: &str) -> Config {
+        let args: Vec<String> = ["minigrep", "--interactive", "-i", query, "poem.txt"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        Config::build_from(&args, &[], None).unwrap()
+    }
+
+    #[test]
+    fn typing_refines_results() {
+        let config = config("");
+        let mut app = poem_app(&config);
+        let mut backend = FakeBackend::new(60, 12, "to⌫o");
+        run_app(&mut backend, &mut app).unwrap();
+
+        assert_eq!(5, backend.frames.len());
+        assert!(backend.frames[0][0].starts_with("> "));
+        assert_eq!("> t  (6 matches)", backend.frames[1][0]);
+        assert_eq!("> to  (4 matches)", backend.frames[2][0]);
+        assert_eq!("> t  (6 matches)", backend.frames[3][0]);
+        assert_eq!("> to  (4 matches)", backend.frames[4][0]);
+        assert_eq!("> poem.txt:3:Are you nobody, too?", backend.frames[4][1]);
+    }
+
+    #[test]
+    fn invalid_query_keeps_session_open() {
+        let args: Vec<String> = ["minigrep", "--interactive", "--fuzzy", "1", &"a".repeat(64), "poem.txt"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let config = Config::build_from(&args, &[], None).unwrap();
+        let mut app = poem_app(&config);
+        let mut backend = FakeBackend::new(200, 6, "b⌫⌫");
+        run_app(&mut backend, &mut app).unwrap();
+
+        // 第 65 个字符让模糊查询太长，删掉之后恢复正常
+        assert_eq!(4, backend.frames.len());
+        assert_eq!(
+            format!("> {}b  (invalid pattern: fuzzy queries can be at most 64 characters long)", "a".repeat(64)),
+            backend.frames[1][0]
+        );
+        assert_eq!(format!("> {}  (0 matches)", "a".repeat(64)), backend.frames[2][0]);
+        assert_eq!(format!("> {}  (0 matches)", "a".repeat(63)), backend.frames[3][0]);
+    }
+
+    #[test]
+    fn scrolling_and_preview() {
+        let config = config("to");
+        let mut app = poem_app(&config);
+        let mut backend = FakeBackend::new(40, 8, "↓↓↓⏎");
+        run_app(&mut backend, &mut app).unwrap();
+
+        // 8 行高的屏幕：1 行查询，3 行结果，1 行分隔线，3 行预览
+        let last = backend.frames.last().unwrap();
+        assert_eq!(8, last.len());
+        assert_eq!("  poem.txt:9:How dreary to be somebody!", last[1]);
+        assert_eq!("> poem.txt:15:To an admiring bog!", last[3]);
+        assert_eq!("-".repeat(40), last[4]);
+        assert_eq!("    14: 成天将自己的大名", last[5]);
+        assert_eq!(">   15: To an admiring bog!", last[6]);
+        assert_eq!(Some("poem.txt:15:To an admiring bog!".to_string()), app.selection());
+    }
+}
@@ -0,0 +1,115 @@
+// 基于 ANSI 转义序列的真实终端，通过 stty 切换到 raw 模式，不依赖第三方库
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::process::Command;
+
+use super::{Backend, Key};
+
+pub struct AnsiTerminal {
+    tty: File,
+    // 进入 raw 模式之前的终端设置，退出时恢复
+    saved: String,
+    // 一次读到多个按键时（比如粘贴），剩下的按键先存起来
+    pending: VecDeque<Key>,
+}
+
+impl AnsiTerminal {
+    pub fn open() -> io::Result<AnsiTerminal> {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+        let saved = stty(&["-g"])?.trim().to_string();
+        stty(&["raw", "-echo"])?;
+
+        let mut terminal = AnsiTerminal {
+            tty,
+            saved,
+            pending: VecDeque::new(),
+        };
+        // 切换到备用屏幕并隐藏光标，退出后原来的终端内容不会被覆盖
+        terminal.tty.write_all(b"\x1b[?1049h\x1b[?25l")?;
+        Ok(terminal)
+    }
+}
+
+impl Drop for AnsiTerminal {
+    fn drop(&mut self) {
+        let _ = self.tty.write_all(b"\x1b[?25h\x1b[?1049l");
+        let _ = self.tty.flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+impl Backend for AnsiTerminal {
+    fn size(&mut self) -> io::Result<(usize, usize)> {
+        // stty size 输出的是 "行数 列数"
+        let size = stty(&["size"])?;
+        let mut parts = size.split_whitespace().map(|part| part.parse::<usize>());
+        match (parts.next(), parts.next()) {
+            (Some(Ok(rows)), Some(Ok(cols))) => Ok((cols, rows)),
+            _ => Ok((80, 24)),
+        }
+    }
+
+    fn draw(&mut self, frame: &[String]) -> io::Result<()> {
+        let mut out = String::from("\x1b[H");
+        for (i, line) in frame.iter().enumerate() {
+            if i > 0 {
+                out.push_str("\r\n");
+            }
+            out.push_str("\x1b[2K");
+            out.push_str(line);
+        }
+        self.tty.write_all(out.as_bytes())?;
+        self.tty.flush()
+    }
+
+    fn read_key(&mut self) -> io::Result<Option<Key>> {
+        let mut buf = [0u8; 64];
+        while self.pending.is_empty() {
+            let n = self.tty.read(&mut buf)?;
+            if n == 0 {
+                return Ok(None);
+            }
+            self.pending.extend(parse_keys(&buf[..n]));
+        }
+        Ok(self.pending.pop_front())
+    }
+}
+
+// raw 模式下方向键等会以转义序列的形式一起到达，其它字节按 UTF-8 字符逐个处理
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    match bytes {
+        b"\x1b[A" | b"\x1bOA" => return vec![Key::Up],
+        b"\x1b[B" | b"\x1bOB" => return vec![Key::Down],
+        b"\x1b[5~" => return vec![Key::PageUp],
+        b"\x1b[6~" => return vec![Key::PageDown],
+        // 单独的 Esc 表示退出，不认识的转义序列直接忽略
+        [0x1b] => return vec![Key::Esc],
+        [0x1b, ..] => return Vec::new(),
+        _ => {}
+    }
+
+    String::from_utf8_lossy(bytes)
+        .chars()
+        .filter_map(|c| match c {
+            '\r' | '\n' => Some(Key::Enter),
+            '\u{3}' => Some(Key::Esc),
+            '\u{7f}' | '\u{8}' => Some(Key::Backspace),
+            '\u{10}' => Some(Key::Up),
+            '\u{e}' => Some(Key::Down),
+            c if !c.is_control() => Some(Key::Char(c)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(File::open("/dev/tty")?)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
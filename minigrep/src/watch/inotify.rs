@@ -0,0 +1,107 @@
+// Linux 的 inotify 接口，直接通过 FFI 调用 libc 中的函数
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::raw::{c_char, c_int};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+extern "C" {
+    fn inotify_init1(flags: c_int) -> c_int;
+    fn inotify_add_watch(fd: c_int, pathname: *const c_char, mask: u32) -> c_int;
+}
+
+const IN_CLOEXEC: c_int = 0o2000000;
+
+const IN_MODIFY: u32 = 0x0000_0002;
+const IN_ATTRIB: u32 = 0x0000_0004;
+const IN_CLOSE_WRITE: u32 = 0x0000_0008;
+const IN_MOVED_FROM: u32 = 0x0000_0040;
+const IN_MOVED_TO: u32 = 0x0000_0080;
+const IN_CREATE: u32 = 0x0000_0100;
+const IN_DELETE: u32 = 0x0000_0200;
+const IN_DELETE_SELF: u32 = 0x0000_0400;
+const IN_MOVE_SELF: u32 = 0x0000_0800;
+// 只会出现在读到的事件中：事件太多被丢掉了，或者监视已经被移除（比如目录被删除）
+const IN_Q_OVERFLOW: u32 = 0x0000_4000;
+const IN_IGNORED: u32 = 0x0000_8000;
+
+// struct inotify_event 固定部分的大小：wd、mask、cookie、len 四个 32 位整数，后面跟着 len 字节的文件名
+const EVENT_SIZE: usize = 16;
+
+const MASK: u32 = IN_MODIFY
+    | IN_ATTRIB
+    | IN_CLOSE_WRITE
+    | IN_MOVED_FROM
+    | IN_MOVED_TO
+    | IN_CREATE
+    | IN_DELETE
+    | IN_DELETE_SELF
+    | IN_MOVE_SELF;
+
+pub struct Inotify {
+    // File 被 drop 的时候会关闭 inotify 的文件描述符
+    file: File,
+    // 每个 watch 监视的目录
+    dirs: HashMap<c_int, PathBuf>,
+}
+
+impl Inotify {
+    pub fn new() -> io::Result<Inotify> {
+        let fd = unsafe { inotify_init1(IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // fd 是刚刚创建的，没有被其它地方持有
+        let file = unsafe { File::from_raw_fd(fd) };
+        Ok(Inotify {
+            file,
+            dirs: HashMap::new(),
+        })
+    }
+
+    // 对同一个目录重复添加是安全的，内核会返回同一个 watch。
+    // 目录被改名后 watch 不变，重新添加时记下新的路径
+    pub fn add_watch(&mut self, path: &Path) -> io::Result<()> {
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        let wd = unsafe { inotify_add_watch(self.file.as_raw_fd(), c_path.as_ptr(), MASK) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.dirs.insert(wd, path.to_path_buf());
+        Ok(())
+    }
+
+    // 阻塞直到有事件发生，返回发生了事件的目录，具体是哪个文件变了由调用者重新检查
+    pub fn wait(&mut self) -> io::Result<Vec<PathBuf>> {
+        let mut buf = [0u8; 4096];
+        let read = self.file.read(&mut buf)?;
+
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        let mut offset = 0;
+        while offset + EVENT_SIZE <= read {
+            let field = |i: usize| {
+                let start = offset + i * 4;
+                u32::from_ne_bytes(buf[start..start + 4].try_into().unwrap())
+            };
+            let (wd, mask, len) = (field(0) as c_int, field(1), field(3) as usize);
+            offset += EVENT_SIZE + len;
+
+            // 丢了事件就不知道哪里变了，所有目录都要检查
+            if mask & IN_Q_OVERFLOW != 0 {
+                return Ok(self.dirs.values().cloned().collect());
+            }
+            let dir = if mask & IN_IGNORED != 0 {
+                self.dirs.remove(&wd)
+            } else {
+                self.dirs.get(&wd).cloned()
+            };
+            if let Some(dir) = dir.filter(|dir| !dirs.contains(dir)) {
+                dirs.push(dir);
+            }
+        }
+        Ok(dirs)
+    }
+}
@@ -0,0 +1,263 @@
+// 监视模式：文件变化后只重新搜索变化了的文件，并输出和上一次结果的差异
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::{search_file, warn, Config, MinigrepError, Outcome};
+
+#[cfg(target_os = "linux")]
+mod inotify;
+
+mod tree;
+use tree::Tree;
+
+// 没有 inotify 时轮询的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// 收到事件后稍等一会儿，让编辑器把文件写完，也能把连续的多个事件合并成一次搜索
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+// 文件的修改时间和大小，用来判断文件有没有变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some(Stamp {
+        modified: metadata.modified().ok(),
+        len: metadata.len(),
+    })
+}
+
+// 每个文件上一次的状态和搜索结果，结果按输出的字节保存，不是 UTF-8 的文件名也能原样输出
+struct Snapshot {
+    stamps: HashMap<PathBuf, Stamp>,
+    results: HashMap<PathBuf, Vec<Vec<u8>>>,
+}
+
+impl Snapshot {
+    // 在可能变化了的文件中找出新增、修改和删除的文件，不在 tree 中的文件已经被删除了
+    fn changed_files(&self, candidates: &[PathBuf], tree: &Tree) -> Vec<PathBuf> {
+        candidates
+            .iter()
+            .filter(|path| {
+                let now = if tree.contains(path) { stamp(path) } else { None };
+                self.stamps.get(*path).copied() != now
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+// 按行比较两次的结果，返回 (删除的行, 新增的行)，重复的行按出现次数计算
+fn diff_lines(old: &[Vec<u8>], new: &[Vec<u8>]) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+    (missing_from(old, new), missing_from(new, old))
+}
+
+// lines 中在 other 里找不到对应的行
+fn missing_from(lines: &[Vec<u8>], other: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut counts: HashMap<&[u8], usize> = HashMap::new();
+    for line in other {
+        *counts.entry(line).or_insert(0) += 1;
+    }
+
+    lines
+        .iter()
+        .filter(|line| match counts.get_mut(line.as_slice()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
+
+// 搜索一个文件，把输出分成一行一行。这个文件出错时和普通搜索一样报告并跳过，
+// 和具体文件无关的错误（比如预处理命令无法运行）直接返回
+fn search_lines(config: &Config, path: &Path) -> Result<Vec<Vec<u8>>, MinigrepError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut buf = Vec::new();
+    match search_file(config, path, &mut buf) {
+        Err(error) if error.path().is_some() => warn(config, &error, &mut io::stderr()),
+        Err(error) => return Err(error),
+        Ok(_) => {}
+    }
+    let mut lines: Vec<Vec<u8>> = buf.split(|&b| b == b'\n').map(<[u8]>::to_vec).collect();
+    // 输出以换行符结尾时最后一段是空的
+    if lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    Ok(lines)
+}
+
+// 等待文件变化：Linux 上使用 inotify，其它平台或者 inotify 不可用时退回到轮询
+enum Waiter {
+    #[cfg(target_os = "linux")]
+    Inotify(inotify::Inotify),
+    Poll,
+}
+
+impl Waiter {
+    fn new() -> Waiter {
+        #[cfg(target_os = "linux")]
+        if let Ok(inotify) = inotify::Inotify::new() {
+            return Waiter::Inotify(inotify);
+        }
+        Waiter::Poll
+    }
+
+    // 监视目录而不是文件本身，这样编辑器用重命名的方式保存文件时也能收到事件
+    fn watch(&mut self, dirs: &[PathBuf]) {
+        #[cfg(target_os = "linux")]
+        if let Waiter::Inotify(inotify) = self {
+            for dir in dirs {
+                let _ = inotify.add_watch(dir);
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = dirs;
+    }
+
+    // 返回需要重新列出的目录。轮询时不知道哪里变了，所有目录都要检查
+    fn wait(&mut self, tree: &Tree) -> io::Result<Vec<PathBuf>> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Waiter::Inotify(inotify) => {
+                let dirs = inotify.wait()?;
+                thread::sleep(DEBOUNCE);
+                Ok(dirs)
+            }
+            Waiter::Poll => {
+                thread::sleep(POLL_INTERVAL);
+                Ok(tree.watched_dirs())
+            }
+        }
+    }
+}
+
+// 一直运行到出错为止，所以不会返回 Ok
+pub fn run(config: &Config) -> Result<Outcome, MinigrepError> {
+    let stdout = io::stdout();
+    let mut messages = io::stderr();
+
+    // 读不了的目录报告之后跳过，其它目录照常监视
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    let mut tree = Tree::new(&config.file_paths, &mut files, &mut errors);
+    for error in &errors {
+        warn(config, error, &mut messages);
+    }
+    let mut waiter = Waiter::new();
+    waiter.watch(&tree.watched_dirs());
+    tree.take_new_dirs();
+
+    // 第一次搜索直接输出所有结果
+    let mut snapshot = Snapshot {
+        stamps: HashMap::new(),
+        results: HashMap::new(),
+    };
+    for path in files {
+        let lines = search_lines(config, &path)?;
+        let mut out = stdout.lock();
+        for line in &lines {
+            out.write_all(line)?;
+            out.write_all(b"\n")?;
+        }
+        out.flush()?;
+        if let Some(stamp) = stamp(&path) {
+            snapshot.stamps.insert(path.clone(), stamp);
+        }
+        snapshot.results.insert(path, lines);
+    }
+
+    loop {
+        // 只重新列出发生了事件的目录，新出现的子目录需要添加监视
+        let mut errors = Vec::new();
+        let mut affected = Vec::new();
+        for dir in waiter.wait(&tree)? {
+            affected.extend(tree.rescan(&dir, &mut errors));
+        }
+        waiter.watch(&tree.take_new_dirs());
+        for error in &errors {
+            warn(config, error, &mut messages);
+        }
+        affected.sort();
+        affected.dedup();
+
+        for path in snapshot.changed_files(&affected, &tree) {
+            let lines = search_lines(config, &path)?;
+            let old = snapshot.results.remove(&path).unwrap_or_default();
+            let (removed, added) = diff_lines(&old, &lines);
+
+            let mut out = stdout.lock();
+            for (marker, lines) in [(b"- ", removed), (b"+ ", added)] {
+                for line in lines {
+                    out.write_all(marker)?;
+                    out.write_all(&line)?;
+                    out.write_all(b"\n")?;
+                }
+            }
+            out.flush()?;
+
+            match stamp(&path) {
+                Some(stamp) if tree.contains(&path) => {
+                    snapshot.stamps.insert(path.clone(), stamp);
+                    snapshot.results.insert(path, lines);
+                }
+                _ => {
+                    snapshot.stamps.remove(&path);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Temp;
+
+    fn lines(list: &[&str]) -> Vec<Vec<u8>> {
+        list.iter().map(|line| line.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn diff_counts_duplicates() {
+        let old = lines(&["a", "b", "b", "c"]);
+        let new = lines(&["b", "c", "d", "c"]);
+        assert_eq!((lines(&["a", "b"]), lines(&["d", "c"])), diff_lines(&old, &new));
+    }
+
+    #[test]
+    fn detects_changed_and_removed_files() {
+        let dir = Temp::dir("watch");
+        let kept = dir.join("kept.txt");
+        let edited = dir.join("edited.txt");
+        fs::write(&kept, "to be\n").unwrap();
+        fs::write(&edited, "to be\n").unwrap();
+
+        let mut snapshot = Snapshot {
+            stamps: HashMap::new(),
+            results: HashMap::new(),
+        };
+        for path in [&kept, &edited] {
+            snapshot.stamps.insert(path.clone(), stamp(path).unwrap());
+        }
+        snapshot.stamps.insert(PathBuf::from("gone.txt"), Stamp { modified: None, len: 0 });
+
+        fs::write(&edited, "to be or not to be\n").unwrap();
+        let tree = Tree::new(&[kept.clone(), edited.clone()], &mut Vec::new(), &mut Vec::new());
+        let candidates = vec![kept, edited.clone(), PathBuf::from("gone.txt")];
+        assert_eq!(vec![edited, PathBuf::from("gone.txt")], snapshot.changed_files(&candidates, &tree));
+    }
+}
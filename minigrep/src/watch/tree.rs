@@ -0,0 +1,205 @@
+// 监视范围内已知的目录和文件。收到事件时只重新列出发生变化的目录，不会每次都遍历整棵目录树
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::MinigrepError;
+
+#[derive(Debug, Default)]
+pub struct Tree {
+    // 命令行中直接给出的文件，不管存不存在都在范围内
+    named: Vec<PathBuf>,
+    // 搜索的目录和它们所有的子目录
+    dirs: BTreeSet<PathBuf>,
+    files: BTreeSet<PathBuf>,
+    // 上一次 take_new_dirs 之后新发现的目录，需要添加监视
+    new_dirs: Vec<PathBuf>,
+}
+
+// 文件所在的目录，监视它才能收到文件被改名、删除或重新创建的事件
+pub fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+// 目录中的文件和子目录，按名字排序，跳过以 . 开头的隐藏文件
+fn list(dir: &Path) -> io::Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let (mut files, mut dirs) = (Vec::new(), Vec::new());
+    for entry in entries {
+        if entry.file_name().as_encoded_bytes().starts_with(b".") {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            dirs.push(entry.path());
+        } else {
+            files.push(entry.path());
+        }
+    }
+    Ok((files, dirs))
+}
+
+// set 中在 dir 下面的路径。路径按组成部分比较，dir 下面的路径在 set 中是连续的一段
+fn under<'a>(set: &'a BTreeSet<PathBuf>, dir: &'a Path) -> impl Iterator<Item = &'a PathBuf> + 'a {
+    set.range(dir.to_path_buf()..).take_while(move |path| path.starts_with(dir))
+}
+
+impl Tree {
+    // 展开命令行中的路径，found 按和 walk_paths 一样的顺序收到所有的文件
+    pub fn new(paths: &[PathBuf], found: &mut Vec<PathBuf>, errors: &mut Vec<MinigrepError>) -> Tree {
+        let mut tree = Tree::default();
+        for path in paths {
+            if path.is_dir() {
+                tree.add_dir(path, found, errors);
+            } else {
+                tree.named.push(path.clone());
+                found.push(path.clone());
+            }
+        }
+        tree
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains(path) || self.named.iter().any(|named| named == path)
+    }
+
+    // 需要监视的目录：所有已知的目录和直接给出的文件所在的目录
+    pub fn watched_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = self.dirs.iter().cloned().collect();
+        for named in &self.named {
+            let parent = parent_dir(named);
+            if !self.dirs.contains(parent) && !dirs.iter().any(|dir| dir == parent) {
+                dirs.push(parent.to_path_buf());
+            }
+        }
+        dirs
+    }
+
+    pub fn take_new_dirs(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.new_dirs)
+    }
+
+    // 第一次遇到的目录，递归地记下其中的子目录和文件
+    fn add_dir(&mut self, dir: &Path, found: &mut Vec<PathBuf>, errors: &mut Vec<MinigrepError>) {
+        self.dirs.insert(dir.to_path_buf());
+        self.new_dirs.push(dir.to_path_buf());
+        match list(dir) {
+            Ok((files, dirs)) => {
+                for file in files {
+                    self.files.insert(file.clone());
+                    found.push(file);
+                }
+                for sub in dirs {
+                    self.add_dir(&sub, found, errors);
+                }
+            }
+            Err(e) => errors.push(MinigrepError::io(dir)(e)),
+        }
+    }
+
+    // 目录不在了，其中所有的文件都算作删除
+    fn remove_dir(&mut self, dir: &Path, affected: &mut Vec<PathBuf>) {
+        let gone: Vec<PathBuf> = under(&self.files, dir).cloned().collect();
+        for file in gone {
+            self.files.remove(&file);
+            affected.push(file);
+        }
+        let gone: Vec<PathBuf> = under(&self.dirs, dir).cloned().collect();
+        for sub in gone {
+            self.dirs.remove(&sub);
+        }
+    }
+
+    // 重新列出发生了变化的目录，返回可能变化了的文件：这个目录中原来的和现在的文件。
+    // 目录读取失败（不是不存在）时保留原来的文件，不会因为一时的错误把它们都当作删除了
+    pub fn rescan(&mut self, dir: &Path, errors: &mut Vec<MinigrepError>) -> Vec<PathBuf> {
+        let mut affected: Vec<PathBuf> = self.named.iter().filter(|named| parent_dir(named) == dir).cloned().collect();
+        if !self.dirs.contains(dir) {
+            return affected;
+        }
+
+        let (files, dirs) = match list(dir) {
+            Ok(listed) => listed,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.remove_dir(dir, &mut affected);
+                return affected;
+            }
+            Err(e) => {
+                errors.push(MinigrepError::io(dir)(e));
+                return affected;
+            }
+        };
+
+        let old_files: Vec<PathBuf> = under(&self.files, dir).filter(|file| file.parent() == Some(dir)).cloned().collect();
+        for file in old_files {
+            if !files.contains(&file) {
+                self.files.remove(&file);
+                affected.push(file);
+            }
+        }
+        let old_dirs: Vec<PathBuf> = under(&self.dirs, dir).filter(|sub| sub.parent() == Some(dir)).cloned().collect();
+        for sub in old_dirs {
+            if !dirs.contains(&sub) {
+                self.remove_dir(&sub, &mut affected);
+            }
+        }
+
+        for file in files {
+            self.files.insert(file.clone());
+            affected.push(file);
+        }
+        // 已有的子目录有自己的监视，只有新出现的（比如整个目录被移动进来）需要遍历
+        for sub in dirs {
+            if !self.dirs.contains(&sub) {
+                self.add_dir(&sub, &mut affected, errors);
+            }
+        }
+        affected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Temp;
+
+    #[test]
+    fn rescans_only_changed_directories() {
+        let dir = Temp::dir("watch-tree");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), "").unwrap();
+        fs::write(dir.join("sub/b.txt"), "").unwrap();
+
+        let mut found = Vec::new();
+        let mut errors = Vec::new();
+        let mut tree = Tree::new(&[dir.to_path_buf()], &mut found, &mut errors);
+        assert_eq!(vec![dir.join("a.txt"), dir.join("sub/b.txt")], found);
+        assert_eq!(vec![dir.to_path_buf(), dir.join("sub")], tree.take_new_dirs());
+
+        // 新建的子目录会被遍历，其中的目录需要添加监视
+        fs::create_dir_all(dir.join("new/deep")).unwrap();
+        fs::write(dir.join("new/deep/c.txt"), "").unwrap();
+        fs::remove_file(dir.join("a.txt")).unwrap();
+        assert_eq!(vec![dir.join("a.txt"), dir.join("new/deep/c.txt")], tree.rescan(&dir, &mut errors));
+        assert_eq!(vec![dir.join("new"), dir.join("new/deep")], tree.take_new_dirs());
+        assert!(!tree.contains(&dir.join("a.txt")) && tree.contains(&dir.join("new/deep/c.txt")));
+
+        // 目录暂时读不了时保留其中的文件，恢复之后照常更新
+        fs::remove_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub"), "").unwrap();
+        assert!(tree.rescan(&dir.join("sub"), &mut errors).is_empty());
+        assert_eq!(1, errors.len());
+        assert!(tree.contains(&dir.join("sub/b.txt")));
+
+        // 目录被删除时其中所有的文件都算作删除
+        fs::remove_dir_all(dir.join("new")).unwrap();
+        assert_eq!(vec![dir.join("new/deep/c.txt")], tree.rescan(&dir.join("new"), &mut errors));
+        assert!(!tree.contains(&dir.join("new/deep/c.txt")));
+        assert!(!tree.watched_dirs().contains(&dir.join("new/deep")));
+    }
+}
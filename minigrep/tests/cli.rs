@@ -0,0 +1,366 @@
+// 端到端测试：运行编译好的 minigrep，检查标准输出、标准错误和退出码
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// 忽略用户的配置文件和 IGNORE_CASE 环境变量，测试结果才不会受运行环境影响
+fn minigrep(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_minigrep"));
+    command.arg("--no-config").args(args).env_remove("IGNORE_CASE");
+    command
+}
+
+fn run(args: &[&str]) -> Output {
+    minigrep(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+// 临时目录，测试结束或失败时都会删除
+struct TempDir(PathBuf);
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn temp_dir(name: &str) -> TempDir {
+    let dir = std::env::temp_dir().join(format!("minigrep-cli-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    TempDir(dir)
+}
+
+#[test]
+fn prints_banner_and_matches() {
+    let output = run(&["to", "poem.txt"]);
+    assert_eq!(Some(0), output.status.code());
+
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Environment variable IGNORE_CASE: not set\n"));
+    assert!(stdout.ends_with(
+        "Searching for \"to\" in file poem.txt: \n\
+         Are you nobody, too?\n\
+         How dreary to be somebody!\n"
+    ));
+    // 启动信息同时输出到标准错误
+    assert!(stderr(&output).starts_with("Running command: "));
+}
+
+#[test]
+fn exit_codes() {
+    let output = run(&["-q", "nobody", "poem.txt"]);
+    assert_eq!(Some(0), output.status.code());
+    assert!(output.stdout.is_empty());
+
+    let output = run(&["-q", "rust", "poem.txt"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(output.stdout.is_empty());
+
+    let output = run(&["to"]);
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("problem parsing arguments: not enough arguments\n", stderr(&output));
+
+    let output = run(&["--max-count", "x", "to", "poem.txt"]);
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("problem parsing arguments: --max-count expects a number, got x\n", stderr(&output));
+}
+
+#[test]
+fn missing_file_does_not_stop_search() {
+    let output = run(&["-l", "nobody", "missing.txt", "poem.txt"]);
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("poem.txt\n", stdout(&output));
+    assert_eq!("minigrep: missing.txt: No such file or directory (os error 2)\n", stderr(&output));
+
+    let output = run(&["-l", "--no-messages", "nobody", "missing.txt"]);
+    assert_eq!(Some(2), output.status.code());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn ignore_case_sources() {
+    // 旧的第三个参数写法
+    let output = run(&["-l", "RUST", "tests/fixtures/bundle.zip", "ig"]);
+    assert_eq!(Some(0), output.status.code());
+
+    let output = minigrep(&["-n", "BODY", "poem.txt"]).env("IGNORE_CASE", "1").output().unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert!(stdout(&output).ends_with("Searching for \"BODY\" in file poem.txt: \n1:I'm nobody! Who are you?\n3:Are you nobody, too?\n9:How dreary to be somebody!\n"));
+
+    // 命令行参数优先于环境变量
+    let output = minigrep(&["-q", "-s", "BODY", "poem.txt"]).env("IGNORE_CASE", "1").output().unwrap();
+    assert_eq!(Some(1), output.status.code());
+
+    let output = minigrep(&["to", "poem.txt"]).env("IGNORE_CASE", "maybe").output().unwrap();
+    assert_eq!(Some(2), output.status.code());
+    assert!(stderr(&output).starts_with("problem parsing arguments: IGNORE_CASE: "));
+}
+
+#[test]
+fn config_file_defaults() {
+    let dir = temp_dir("config");
+    let config = dir.join("config");
+    fs::write(&config, "# 默认忽略大小写\n--ignore-case\n").unwrap();
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_minigrep"));
+    let output = command
+        .args(["-l", "NOBODY", "poem.txt"])
+        .env("MINIGREP_CONFIG_PATH", &config)
+        .env_remove("IGNORE_CASE")
+        .output()
+        .unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("poem.txt\n", stdout(&output));
+
+    // -- 之后的 --no-config 是文件名，配置文件仍然生效
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["-l", "--", "NOBODY", "poem.txt", "--no-config"])
+        .env("MINIGREP_CONFIG_PATH", &config)
+        .env_remove("IGNORE_CASE")
+        .output()
+        .unwrap();
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("poem.txt\n", stdout(&output));
+    assert!(stderr(&output).starts_with("minigrep: --no-config: "));
+
+    // 明确指定的配置文件不存在时报错
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["-l", "to", "poem.txt"])
+        .env("MINIGREP_CONFIG_PATH", dir.join("missing"))
+        .output()
+        .unwrap();
+    assert_eq!(Some(2), output.status.code());
+
+}
+
+#[test]
+fn directories_and_compressed_files() {
+    // 没有 --search-archives 时 tar 文件按普通文件搜索，解压之后的 .tar.gz 也一样
+    let output = run(&["-l", "-z", "-i", "NOBODY", "tests/fixtures"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        "tests/fixtures/bundle.tar\n\
+         tests/fixtures/bundle.tar.gz\n\
+         tests/fixtures/poem.txt.gz\n\
+         tests/fixtures/stored.txt.gz\n",
+        stdout(&output)
+    );
+
+    let output = run(&["-0", "-l", "--search-archives", "productive", "tests/fixtures/bundle.zip"]);
+    assert_eq!("tests/fixtures/bundle.zip!/rust.txt\0", stdout(&output));
+}
+
+#[test]
+fn stats_go_to_stderr() {
+    let output = run(&["-q", "--stats", "nobody", "poem.txt"]);
+    assert_eq!(Some(0), output.status.code());
+    assert!(output.stdout.is_empty());
+    let stderr = stderr(&output);
+    assert!(stderr.contains("files matched: 1\n"));
+    assert!(stderr.contains("lines matched: 1\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn searches_named_pipes() {
+    let dir = temp_dir("fifo");
+    let fifo = dir.join("input").to_string_lossy().into_owned();
+    let _ = fs::remove_file(&fifo);
+    assert!(Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+
+    // 打开管道的写端会等到 minigrep 打开读端
+    let search_in = |contents: &'static str, flags: &[&str]| {
+        let writer = {
+            let fifo = fifo.clone();
+            thread::spawn(move || fs::write(&fifo, contents).unwrap())
+        };
+        let mut args = flags.to_vec();
+        args.extend_from_slice(&["-n", "you", &fifo]);
+        let output = run(&args);
+        writer.join().unwrap();
+        output
+    };
+    let search = |flags: &[&str]| search_in("one you\ntwo\nthree you\n", flags);
+
+    let output = search(&["--stats"]);
+    assert_eq!(Some(0), output.status.code());
+    assert!(stdout(&output).ends_with("1:one you\n3:three you\n"));
+    assert!(stderr(&output).contains("bytes searched: 22\n"));
+
+    // 判断是不是归档时看过的开头仍然要搜索
+    let output = search(&["--search-archives"]);
+    assert_eq!(Some(0), output.status.code());
+    assert!(stdout(&output).ends_with("1:one you\n3:three you\n"));
+
+    // 管道不能二分查找，从头读并跳过范围之前的行
+    let log = "2024-05-01T09:00:00 you early\n2024-05-01T11:00:00 you late\n";
+    let output = search_in(log, &["--since", "2024-05-01T10"]);
+    assert_eq!(Some(0), output.status.code());
+    assert!(stdout(&output).ends_with("\n2:2024-05-01T11:00:00 you late\n"));
+
+}
+
+#[cfg(unix)]
+#[test]
+fn preprocessor_output_is_searched() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("pre");
+    let script = |name: &str, body: &str| {
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    };
+    let upper = script("upper.sh", "tr a-z A-Z");
+    let broken = script("broken.sh", "echo \"cannot convert $1\" >&2; exit 3");
+    let files = dir.join("files");
+    fs::create_dir_all(&files).unwrap();
+    fs::write(files.join("notes.txt"), "hello world\n").unwrap();
+    fs::write(files.join("raw.log"), "hello log\n").unwrap();
+    let files = files.to_string_lossy().into_owned();
+    let notes = format!("{}/notes.txt", files);
+
+    // 只有 .txt 文件经过转换
+    let output = run(&["--pre", &upper, "--pre-glob", "*.txt", "HELLO", &files]);
+    assert_eq!(Some(0), output.status.code());
+    assert!(stdout(&output).ends_with(&format!("{}:HELLO WORLD\n", notes)));
+
+    let output = run(&["-l", "--pre", &upper, "hello", &files]);
+    assert_eq!(Some(1), output.status.code());
+
+    // 预处理器失败只影响这一个文件
+    let output = run(&["-l", "--pre", &broken, "--pre-glob", "*.txt", "hello", &files]);
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!(format!("{}/raw.log\n", files), stdout(&output));
+    assert_eq!(
+        format!("cannot convert {}\nminigrep: {}: preprocessor {} failed: exit status: 3\n", notes, notes, broken),
+        stderr(&output)
+    );
+
+    // 命令无法运行时每个文件都会失败，报告一次命令的问题就结束
+    let missing = dir.join("missing.sh").to_string_lossy().into_owned();
+    let output = run(&["--pre", &missing, "hello", &files]);
+    assert_eq!(Some(2), output.status.code());
+    assert!(stderr(&output).ends_with(&format!(
+        "Application error: cannot run preprocessor {}: No such file or directory (os error 2)\n",
+        missing
+    )));
+
+    let output = run(&["--pre-glob", "*.txt", "hello", &files]);
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("problem parsing arguments: --pre-glob requires --pre\n", stderr(&output));
+
+}
+
+#[test]
+fn column_is_reported_without_a_terminal() {
+    // 输出到管道时没有颜色，但 --column 仍然给出最佳匹配的位置
+    let output = run(&["--fuzzy", "1", "--column", "-n", "nobady", "poem.txt"]);
+    assert_eq!(Some(0), output.status.code());
+    assert!(stdout(&output).ends_with("1:5:I'm nobody! Who are you?\n3:9:Are you nobody, too?\n"));
+    assert!(!stdout(&output).contains('\x1b'));
+
+    let output = run(&["--column", "-i", "YOU", "poem.txt"]);
+    assert!(stdout(&output).ends_with(
+        "21:I'm nobody! Who are you?\n5:Are you nobody, too?\n19:They'd banish us, you know.\n9:To tell your name the livelong day\n"
+    ));
+
+    let output = run(&["--column", "--expr", "a & b", "poem.txt"]);
+    assert_eq!(Some(2), output.status.code());
+}
+
+#[test]
+fn follow_stops_at_max_count() {
+    // 读完已有内容时已经有两个匹配，-m 2 应该立刻结束，而不是一直等下去
+    let mut child = minigrep(&["--follow", "-m", "2", "you", "poem.txt"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while child.try_wait().unwrap().is_none() {
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("--follow ignored -m");
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert!(stdout(&output).ends_with("I'm nobody! Who are you?\nAre you nobody, too?\n"));
+}
+
+#[test]
+fn index_subcommand() {
+    let dir = temp_dir("index");
+    fs::write(dir.join("poem.txt"), fs::read("poem.txt").unwrap()).unwrap();
+    fs::write(dir.join("rust.txt"), "Rust:\nsafe, fast, productive.\n").unwrap();
+    let root = dir.to_string_lossy().into_owned();
+
+    // 子命令前面可以有全局选项，run 已经加上了 --no-config
+    let output = run(&["index", &root]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(format!("indexed 2 files in {} (2 updated)\n", root), stdout(&output));
+
+    // 没有变化的文件不会重新读取
+    fs::write(dir.join("rust.txt"), "Rust:\nsafe, fast, productive, nobody.\n").unwrap();
+    let output = run(&["-q", "index", &root]);
+    assert_eq!(format!("indexed 2 files in {} (1 updated)\n", root), stdout(&output));
+
+    let output = run(&["-l", "--index", "nobody", &root]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(format!("{0}/poem.txt\n{0}/rust.txt\n", root), stdout(&output));
+    let output = run(&["-l", "--index", "productive", &root]);
+    assert_eq!(format!("{}/rust.txt\n", root), stdout(&output));
+
+    // -- 之后的 index 是查询
+    let output = run(&["-l", "--", "index", &root]);
+    assert_eq!(Some(1), output.status.code());
+
+    let output = run(&["index", "poem.txt"]);
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("Application error: cannot index poem.txt: not a directory\n", stderr(&output));
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_file_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = temp_dir("latin1");
+    let file = dir.join(OsStr::from_bytes(b"caf\xe9.txt"));
+    fs::write(&file, "I'm nobody! Who are you?\n").unwrap();
+    let mut expected = file.as_os_str().as_bytes().to_vec();
+
+    // 目录中的文件名原样输出，不会被替换成 U+FFFD 之后再也打不开
+    let output = minigrep(&["-n", "nobody"]).arg(&*dir).output().unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert!(output.stdout.ends_with(&[&expected[..], b":1:I'm nobody! Who are you?\n"].concat()));
+
+    // 命令行参数中的文件名也可以不是 UTF-8
+    let output = minigrep(&["-0", "-l", "nobody"]).arg(&file).output().unwrap();
+    assert_eq!(Some(0), output.status.code());
+    expected.push(b'\0');
+    assert_eq!(expected, output.stdout);
+}
@@ -0,0 +1,112 @@
+// 随机生成查询和内容，检查搜索函数之间应该始终成立的关系。
+// 不依赖第三方库，用固定种子的伪随机数生成器，失败时可以稳定复现
+use minigrep::{search, search_case_insensitive, LineMatcher};
+
+const CASES: usize = 5000;
+
+// xorshift64*，足够用来生成测试数据
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    // 字符集中故意放了大小写转换比较特殊的字符：
+    // ß 的大写是 SS，İ 的小写是两个字符，K 是开尔文符号，Σ 在词尾的小写是 ς
+    fn text(&mut self, max_len: usize) -> String {
+        const CHARS: &[char] = &[
+            'a', 'b', 'A', 'B', 'r', 'R', 'u', 's', 't', 'T', ' ', '\n', '\r', 'ß', 'S', 'İ', 'i', 'I', 'K', 'k',
+            'Σ', 'σ', 'ς', 'É', 'é', '大', '人',
+        ];
+        let len = self.below(max_len + 1);
+        (0..len).map(|_| CHARS[self.below(CHARS.len())]).collect()
+    }
+}
+
+// 查询多数时候取自内容本身，这样才会经常有匹配
+fn cases() -> impl Iterator<Item = (String, String)> {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    (0..CASES).map(move |_| {
+        let contents = rng.text(40);
+        let chars: Vec<char> = contents.chars().filter(|&c| c != '\n' && c != '\r').collect();
+        let query = if !chars.is_empty() && rng.below(4) != 0 {
+            let start = rng.below(chars.len());
+            let end = start + rng.below((chars.len() - start).min(4)) + 1;
+            chars[start..end].iter().collect()
+        } else {
+            rng.text(3).replace(['\n', '\r'], "")
+        };
+        (query, contents)
+    })
+}
+
+// a 是不是 b 的子序列：忽略大小写的结果要包含区分大小写的所有结果，并且顺序一致
+fn is_subsequence(a: &[&str], b: &[&str]) -> bool {
+    let mut b = b.iter();
+    a.iter().all(|line| b.any(|other| other == line))
+}
+
+#[test]
+fn case_insensitive_is_superset() {
+    for (query, contents) in cases() {
+        let sensitive = search(&query, &contents);
+        let insensitive = search_case_insensitive(&query, &contents);
+        assert!(
+            is_subsequence(&sensitive, &insensitive),
+            "query {:?} contents {:?}: {:?} is not contained in {:?}",
+            query,
+            contents,
+            sensitive,
+            insensitive
+        );
+    }
+}
+
+#[test]
+fn search_matches_every_containing_line() {
+    for (query, contents) in cases() {
+        let expected: Vec<&str> = contents.lines().filter(|line| line.contains(query.as_str())).collect();
+        assert_eq!(expected, search(&query, &contents), "query {:?} contents {:?}", query, contents);
+    }
+}
+
+// 逐行搜索文件时用的 LineMatcher 和 search_case_insensitive 的结果要一致
+#[test]
+fn line_matcher_agrees_with_search() {
+    for (query, contents) in cases() {
+        let matcher = LineMatcher::new(&query, true);
+        let expected: Vec<&str> = contents.lines().filter(|line| matcher.is_match(line)).collect();
+        assert_eq!(
+            expected,
+            search_case_insensitive(&query, &contents),
+            "query {:?} contents {:?}",
+            query,
+            contents
+        );
+    }
+}
+
+// find 报告的位置和 is_match 一致，并且这段内容本身就匹配查询
+#[test]
+fn literal_spans_match_the_query() {
+    for (query, contents) in cases() {
+        for ignore_case in [false, true] {
+            let matcher = LineMatcher::new(&query, ignore_case);
+            for line in contents.lines() {
+                let span = matcher.find(line);
+                assert_eq!(matcher.is_match(line), span.is_some(), "query {:?} line {:?}", query, line);
+                if let Some((start, end)) = span {
+                    assert!(matcher.is_match(&line[start..end]), "query {:?} line {:?} span {:?}", query, line, span);
+                }
+            }
+        }
+    }
+}